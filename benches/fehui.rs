@@ -0,0 +1,196 @@
+//! Benchmarks for building, layouting and state lookup of synthetic widget trees.
+//!
+//! Besides the timings reported by criterion, the number of heap allocations for a single
+//! pass is printed for each benchmark.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use fehui::widgets::Row;
+use fehui::{Build, FehUI, Key, Layout, Layouter, View, Widget};
+use std::alloc::{GlobalAlloc, Layout as AllocLayout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: AllocLayout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: AllocLayout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn count_allocations<F: FnOnce()>(name: &str, f: F) {
+    let start = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    let end = ALLOCATIONS.load(Ordering::Relaxed);
+    println!("{}: {} allocations", name, end - start);
+}
+
+const SIZES: &[usize] = &[10, 100, 1000];
+
+/// Linear chain of `depth` pass-through widgets.
+#[derive(Debug)]
+struct Deep {
+    depth: usize,
+}
+
+impl Widget for Deep {
+    fn build(&self, mut ctxt: Build) {
+        if self.depth > 0 {
+            unsafe {
+                ctxt.add(
+                    Deep {
+                        depth: self.depth - 1,
+                    },
+                    None,
+                )
+            };
+        } else {
+            unsafe { ctxt.add(Row { children: vec![] }, None) };
+        }
+    }
+
+    fn layout(&self, _: Layouter) -> Layout {
+        Layout::Pass
+    }
+}
+
+fn wide(width: usize) -> Row {
+    Row {
+        children: (0..width)
+            .map(|_| View::from(Row { children: vec![] }))
+            .collect(),
+    }
+}
+
+/// Adds a single keyed child, used to create many keyed siblings inside a `Row`.
+#[derive(Debug)]
+struct Keyed {
+    key: usize,
+    stateful: bool,
+}
+
+impl Widget for Keyed {
+    fn build(&self, mut ctxt: Build) {
+        let leaf = Leaf {
+            stateful: self.stateful,
+        };
+        unsafe { ctxt.add(leaf, Some(Key::Global(self.key + 1))) };
+    }
+
+    fn layout(&self, _: Layouter) -> Layout {
+        Layout::Pass
+    }
+}
+
+#[derive(Debug)]
+struct Leaf {
+    stateful: bool,
+}
+
+impl Widget for Leaf {
+    fn build(&self, mut ctxt: Build) {
+        if self.stateful {
+            let state = ctxt.create_state(|| 0usize);
+            *state.borrow_mut().downcast_mut::<usize>().unwrap() += 1;
+        }
+    }
+
+    fn layout(&self, _: Layouter) -> Layout {
+        Layout::Pass
+    }
+}
+
+fn keyed(width: usize, stateful: bool) -> Row {
+    Row {
+        children: (0..width)
+            .map(|key| View::from(Keyed { key, stateful }))
+            .collect(),
+    }
+}
+
+fn bench_build(c: &mut Criterion) {
+    for &n in SIZES {
+        let mut ui = FehUI::new();
+        count_allocations(&format!("build/deep/{}", n), || ui.build(Deep { depth: n }));
+        c.bench_function(&format!("build/deep/{}", n), move |b| {
+            b.iter(|| ui.build(Deep { depth: n }))
+        });
+
+        let mut ui = FehUI::new();
+        count_allocations(&format!("build/wide/{}", n), || ui.build(wide(n)));
+        c.bench_function(&format!("build/wide/{}", n), move |b| {
+            b.iter(|| ui.build(wide(n)))
+        });
+
+        let mut ui = FehUI::new();
+        count_allocations(&format!("build/keyed/{}", n), || ui.build(keyed(n, false)));
+        c.bench_function(&format!("build/keyed/{}", n), move |b| {
+            b.iter(|| ui.build(keyed(n, false)))
+        });
+    }
+}
+
+fn bench_layout(c: &mut Criterion) {
+    for &n in SIZES {
+        // Full layout including layout tree generation after a rebuild, the build itself
+        // isn't timed.
+        let mut ui = FehUI::new();
+        ui.build(wide(n));
+        count_allocations(&format!("layout/generate/{}", n), || {
+            ui.layout(800.0, 600.0)
+        });
+        c.bench_function(&format!("layout/generate/{}", n), move |b| {
+            b.iter_batched(
+                || {
+                    let mut ui = FehUI::new();
+                    ui.build(wide(n));
+                    ui
+                },
+                |mut ui| {
+                    ui.layout(800.0, 600.0);
+                    ui
+                },
+                BatchSize::SmallInput,
+            )
+        });
+
+        // Relayout of an unchanged widget tree (e.g. window resize).
+        let mut ui = FehUI::new();
+        ui.build(wide(n));
+        ui.layout(800.0, 600.0);
+        count_allocations(&format!("layout/resize/{}", n), || {
+            ui.layout(640.0, 480.0)
+        });
+        let mut width = 640.0;
+        c.bench_function(&format!("layout/resize/{}", n), move |b| {
+            b.iter(|| {
+                width = if width == 640.0 { 800.0 } else { 640.0 };
+                ui.layout(width, 480.0);
+            })
+        });
+    }
+}
+
+fn bench_state(c: &mut Criterion) {
+    for &n in SIZES {
+        let mut ui = FehUI::new();
+        // First pass creates the states, further passes only look them up.
+        ui.build(keyed(n, true));
+        count_allocations(&format!("state/lookup/{}", n), || ui.build(keyed(n, true)));
+        c.bench_function(&format!("state/lookup/{}", n), move |b| {
+            b.iter(|| ui.build(keyed(n, true)))
+        });
+    }
+}
+
+criterion_group!(benches, bench_build, bench_layout, bench_state);
+criterion_main!(benches);
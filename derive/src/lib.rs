@@ -1,21 +1,28 @@
 #![recursion_limit = "128"]
 
 extern crate proc_macro;
+
+mod props;
+
 use darling::FromDeriveInput;
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, ItemStruct};
+use proc_macro2::{Ident, Span};
+use quote::{quote, quote_spanned};
+use syn::{
+    parse_macro_input, parse_quote, DeriveInput, GenericParam, Generics, Lit, LitStr, Meta,
+    WhereClause,
+};
 
 enum BuildPath {
-    Str(String),
+    Str(String, Span),
     Default,
 }
 
 impl BuildPath {
-    fn as_str(&self) -> &str {
+    fn ident(&self) -> Ident {
         match self {
-            BuildPath::Str(s) => s.as_str(),
-            BuildPath::Default => "build",
+            BuildPath::Str(s, span) => Ident::new(s, *span),
+            BuildPath::Default => Ident::new("build", Span::call_site()),
         }
     }
 }
@@ -24,31 +31,84 @@ impl darling::FromMeta for BuildPath {
     fn from_word() -> darling::Result<Self> {
         Ok(BuildPath::Default)
     }
-    fn from_string(s: &str) -> darling::Result<Self> {
-        Ok(BuildPath::Str(s.to_string()))
+    fn from_value(value: &Lit) -> darling::Result<Self> {
+        let (name, span) = fn_name(value)?;
+        Ok(BuildPath::Str(name, span))
     }
 }
 
 enum StatePath {
-    Str(String),
-    Default,
+    Str(String, Span),
+    /// Span of the `state` word in the attribute.
+    Default(Span),
 }
 
 impl StatePath {
-    fn as_str(&self) -> &str {
+    fn ident(&self) -> Ident {
         match self {
-            StatePath::Str(s) => s.as_str(),
-            StatePath::Default => "state",
+            StatePath::Str(s, span) => Ident::new(s, *span),
+            StatePath::Default(span) => Ident::new("state", *span),
         }
     }
 }
 
 impl<'a> darling::FromMeta for StatePath {
-    fn from_word() -> darling::Result<Self> {
-        Ok(StatePath::Default)
+    fn from_meta(item: &Meta) -> darling::Result<Self> {
+        match item {
+            Meta::Word(word) => Ok(StatePath::Default(word.span())),
+            Meta::NameValue(value) => Self::from_value(&value.lit),
+            Meta::List(_) => Err(darling::Error::unsupported_format("list").with_span(item)),
+        }
     }
-    fn from_string(s: &str) -> darling::Result<Self> {
-        Ok(StatePath::Str(s.to_string()))
+    fn from_value(value: &Lit) -> darling::Result<Self> {
+        let (name, span) = fn_name(value)?;
+        Ok(StatePath::Str(name, span))
+    }
+}
+
+/// Custom method, e.g. `layout = "my_layout"`.
+struct MethodPath(String, Span);
+
+impl MethodPath {
+    fn ident(&self) -> Ident {
+        Ident::new(&self.0, self.1)
+    }
+}
+
+impl darling::FromMeta for MethodPath {
+    fn from_value(value: &Lit) -> darling::Result<Self> {
+        let (name, span) = fn_name(value)?;
+        Ok(MethodPath(name, span))
+    }
+}
+
+/// Trait bounds overriding the generated ones, e.g. `bound = "T: Widget"`.
+struct Bound(LitStr);
+
+impl darling::FromMeta for Bound {
+    fn from_value(value: &Lit) -> darling::Result<Self> {
+        match value {
+            Lit::Str(s) => Ok(Bound(s.clone())),
+            _ => Err(darling::Error::unexpected_lit_type(value).with_span(value)),
+        }
+    }
+}
+
+/// Parse a method name from a string literal, e.g. `build = "my_build"`.
+fn fn_name(value: &Lit) -> darling::Result<(String, Span)> {
+    match value {
+        Lit::Str(s) => {
+            let name = s.value();
+            if syn::parse_str::<Ident>(&name).is_err() {
+                return Err(darling::Error::custom(format!(
+                    "`{}` is not a valid method name",
+                    name
+                ))
+                .with_span(s));
+            }
+            Ok((name, s.span()))
+        }
+        _ => Err(darling::Error::unexpected_lit_type(value).with_span(value)),
     }
 }
 
@@ -58,41 +118,175 @@ struct WidgetOptions {
     build: BuildPath,
     #[darling(default)]
     state: Option<StatePath>,
+    #[darling(default)]
+    layout: Option<MethodPath>,
+    /// Event handler `fn(&self, &mut EventCtxt, &Event) -> bool`, e.g. `event = "on_event"`.
+    #[darling(default)]
+    event: Option<MethodPath>,
+    /// Keep the state across hot reloads (requires `serde` and the `persist` feature).
+    #[darling(default)]
+    persist: bool,
+    /// Skip rebuilding if the widget equals the previous one (requires `PartialEq`).
+    #[darling(default)]
+    memo: bool,
+    /// Override the generated trait bounds of generic widgets, e.g. `bound = "T: Widget"`.
+    #[darling(default)]
+    bound: Option<Bound>,
+}
+
+/// Add the bounds required by `Widget` to all generic parameters of the widget.
+///
+/// Type parameters need to be `'static + Debug` (and `PartialEq` for memoized widgets),
+/// lifetime parameters need to outlive `'static`.
+fn add_bounds(generics: &mut Generics, memo: bool) {
+    let mut lifetimes = Vec::new();
+    for param in &mut generics.params {
+        match param {
+            GenericParam::Type(ty) => {
+                ty.bounds.push(parse_quote!('static));
+                ty.bounds.push(parse_quote!(std::fmt::Debug));
+                if memo {
+                    ty.bounds.push(parse_quote!(std::cmp::PartialEq));
+                }
+            }
+            GenericParam::Lifetime(lt) => lifetimes.push(lt.lifetime.clone()),
+            GenericParam::Const(_) => (),
+        }
+    }
+
+    let where_clause = generics.make_where_clause();
+    for lifetime in lifetimes {
+        where_clause.predicates.push(parse_quote!(#lifetime: 'static));
+    }
+}
+
+/// Parse a user provided bound override, e.g. `T: Debug + 'static, U: Widget`.
+fn parse_bound(bound: &Bound) -> darling::Result<WhereClause> {
+    syn::parse_str(&format!("where {}", bound.0.value())).map_err(|err| {
+        darling::Error::custom(format!("invalid `bound` attribute: {}", err))
+            .with_span(&bound.0)
+    })
 }
 
 #[proc_macro_derive(Widget, attributes(widget))]
 pub fn widget(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input);
-    let parser: WidgetOptions = FromDeriveInput::from_derive_input(&input).unwrap();
+    let input: DeriveInput = parse_macro_input!(input);
+    let parser: WidgetOptions = match FromDeriveInput::from_derive_input(&input) {
+        Ok(parser) => parser,
+        Err(err) => return err.write_errors().into(),
+    };
 
     let ident = &input.ident;
 
-    let build_fn = proc_macro2::Ident::new(parser.build.as_str(), proc_macro2::Span::call_site());
+    let mut generics = input.generics.clone();
+    match parser.bound {
+        Some(ref bound) => match parse_bound(bound) {
+            Ok(bound) => generics
+                .make_where_clause()
+                .predicates
+                .extend(bound.predicates),
+            Err(err) => return err.write_errors().into(),
+        },
+        None => add_bounds(&mut generics, parser.memo),
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let build_fn = parser.build.ident();
+    let layout_fn = match parser.layout {
+        Some(ref layout) => {
+            let layout_fn = layout.ident();
+            quote_spanned!(layout_fn.span()=>
+                fn layout(&self, ctxt: Layouter) -> Layout {
+                    Self::#layout_fn(self, ctxt).into()
+                }
+            )
+        }
+        None => quote!(
+            fn layout(&self, _: Layouter) -> Layout {
+                Layout::Pass
+            }
+        ),
+    };
+    let event_fn = match parser.event {
+        Some(ref event) => {
+            let event_fn = event.ident();
+            quote_spanned!(event_fn.span()=>
+                fn event(&self, ctxt: &mut fehui::EventCtxt, event: &fehui::Event) -> bool {
+                    Self::#event_fn(self, ctxt, event)
+                }
+            )
+        }
+        None => quote!(),
+    };
+    let memo_fn = if parser.memo {
+        quote!(
+            fn memo(&self, prev: &Widget) -> bool {
+                fehui::memo_eq(self, prev)
+            }
+        )
+    } else {
+        quote!()
+    };
 
     TokenStream::from(match parser.state {
         Some(state) => {
-            let state_fn = proc_macro2::Ident::new(state.as_str(), proc_macro2::Span::call_site());
-            quote!(impl Widget for #ident {
+            let state_fn = state.ident();
+            // Typed bindings check the signatures, errors will point to the attribute.
+            // The methods are only called through these to avoid follow-up errors.
+            let check_state = quote_spanned!(state_fn.span()=>
+                let state_fn: fn(&Self) -> _ = Self::#state_fn;
+            );
+            let check_build = quote_spanned!(build_fn.span()=>
+                let build_fn: fn(&Self, &_, &mut Build) -> _ = Self::#build_fn;
+            );
+            let create_state = if parser.persist {
+                quote!(create_persistent_state)
+            } else {
+                quote!(create_state)
+            };
+            quote!(impl #impl_generics Widget for #ident #ty_generics #where_clause {
                 fn build(&self, mut ctxt: Build) {
-                    let state_ref = ctxt.create_state(|| Self::#state_fn(self));
+                    #check_state
+                    #check_build
+                    let state_ref = ctxt.#create_state(|| state_fn(self));
                     let state_borrow = state_ref.borrow();
                     let state_inner = state_borrow.downcast_ref().unwrap();
-                    let repr = Self::#build_fn(self, state_inner, &mut ctxt);
-                    unsafe { ctxt.add(repr, None); }
+                    let repr = build_fn(self, state_inner, &mut ctxt);
+                    fehui::IntoChildren::add_children(repr, &mut ctxt);
                 }
-                fn layout(&self, _: Layouter) -> Layout {
-                    Layout::Pass
+                #layout_fn
+                #memo_fn
+                #event_fn
+            })
+        }
+        None => {
+            let check_build = quote_spanned!(build_fn.span()=>
+                let build_fn: fn(&Self, &mut Build) -> _ = Self::#build_fn;
+            );
+            quote!(impl #impl_generics Widget for #ident #ty_generics #where_clause {
+                fn build(&self, mut ctxt: Build) {
+                    #check_build
+                    let repr = build_fn(self, &mut ctxt);
+                    fehui::IntoChildren::add_children(repr, &mut ctxt);
                 }
+                #layout_fn
+                #memo_fn
+                #event_fn
             })
         }
-        None => quote!(impl Widget for #ident {
-            fn build(&self, mut ctxt: Build) {
-                let repr = Self::#build_fn(self, &mut ctxt);
-                unsafe { ctxt.add(repr, None); }
-            }
-            fn layout(&self, _: Layouter) -> Layout {
-                Layout::Pass
-            }
-        }),
     })
 }
+
+/// Typed builder for widget props.
+///
+/// Generates `Widget::builder()` with a setter per field and a `build` method, which is only
+/// available after all required fields are set. Fields can be annotated with
+/// `#[prop(default)]`, `#[prop(default = "expr")]`, `#[prop(optional)]` and `#[prop(into)]`.
+#[proc_macro_derive(Props, attributes(prop))]
+pub fn props(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = parse_macro_input!(input);
+    match props::derive(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.write_errors().into(),
+    }
+}
@@ -0,0 +1,309 @@
+//! `#[derive(Props)]`: typed builder for widget structs.
+//!
+//! Required fields are tracked in the type of the builder, `build` is only available
+//! after all of them have been set.
+
+use darling::{ast, FromDeriveInput, FromField};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use syn::{DeriveInput, Expr, GenericArgument, GenericParam, Lit, PathArguments, Type};
+
+enum PropDefault {
+    Expr(Expr),
+    Default,
+}
+
+impl darling::FromMeta for PropDefault {
+    fn from_word() -> darling::Result<Self> {
+        Ok(PropDefault::Default)
+    }
+    fn from_value(value: &Lit) -> darling::Result<Self> {
+        match value {
+            Lit::Str(s) => syn::parse_str(&s.value())
+                .map(PropDefault::Expr)
+                .map_err(|err| {
+                    darling::Error::custom(format!("invalid default expression: {}", err))
+                        .with_span(s)
+                }),
+            _ => Err(darling::Error::unexpected_lit_type(value).with_span(value)),
+        }
+    }
+}
+
+#[derive(FromField)]
+#[darling(attributes(prop))]
+struct PropField {
+    ident: Option<Ident>,
+    ty: Type,
+    /// Use `Default::default()` or the given expression if not set.
+    #[darling(default)]
+    default: Option<PropDefault>,
+    /// Setter accepts any `Into<T>`.
+    #[darling(default)]
+    into: bool,
+    /// `Option<T>` field, setter takes `T` and defaults to `None`.
+    #[darling(default)]
+    optional: bool,
+}
+
+impl PropField {
+    fn is_required(&self) -> bool {
+        self.default.is_none() && !self.optional
+    }
+
+    // Type accepted by the setter.
+    fn value_ty(&self) -> darling::Result<&Type> {
+        if !self.optional {
+            return Ok(&self.ty);
+        }
+
+        option_inner(&self.ty).ok_or_else(|| {
+            darling::Error::custom("`optional` props need to be of type `Option<T>`")
+                .with_span(&self.ty)
+        })
+    }
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let path = match ty {
+        Type::Path(path) if path.qself.is_none() => &path.path,
+        _ => return None,
+    };
+    let segment = path.segments.iter().last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[derive(FromDeriveInput)]
+#[darling(attributes(prop), supports(struct_named))]
+struct PropsOptions {
+    ident: Ident,
+    vis: syn::Visibility,
+    generics: syn::Generics,
+    data: ast::Data<(), PropField>,
+}
+
+pub fn derive(input: &DeriveInput) -> darling::Result<TokenStream> {
+    let options = PropsOptions::from_derive_input(input)?;
+    let fields = options.data.take_struct().unwrap().fields;
+
+    let ident = &options.ident;
+    let vis = &options.vis;
+    let builder = Ident::new(&format!("{}Builder", ident), Span::call_site());
+
+    // Generics of the widget struct, reused for the builder.
+    let (_, ty_generics, where_clause) = options.generics.split_for_impl();
+    let params = options
+        .generics
+        .params
+        .iter()
+        .cloned()
+        .map(|mut param| {
+            if let GenericParam::Type(ref mut ty) = param {
+                ty.eq_token = None;
+                ty.default = None;
+            }
+            param
+        })
+        .collect::<Vec<_>>();
+    let args = options
+        .generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Type(ty) => {
+                let ident = &ty.ident;
+                quote!(#ident)
+            }
+            GenericParam::Lifetime(lt) => {
+                let lifetime = &lt.lifetime;
+                quote!(#lifetime)
+            }
+            GenericParam::Const(c) => {
+                let ident = &c.ident;
+                quote!(#ident)
+            }
+        })
+        .collect::<Vec<_>>();
+    // Generic parameters might only be used by required fields, which are replaced by the
+    // state parameters in the builder.
+    let markers = options
+        .generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Type(ty) => {
+                let ident = &ty.ident;
+                Some(quote!(#ident))
+            }
+            GenericParam::Lifetime(lt) => {
+                let lifetime = &lt.lifetime;
+                Some(quote!(&#lifetime ()))
+            }
+            GenericParam::Const(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    let names = fields
+        .iter()
+        .map(|field| field.ident.clone().unwrap())
+        .collect::<Vec<_>>();
+    let required = fields
+        .iter()
+        .map(PropField::is_required)
+        .collect::<Vec<_>>();
+    // Type parameters tracking the state of required fields: `()` if unset, `(T,)` if set.
+    let states = names
+        .iter()
+        .zip(&required)
+        .filter(|(_, required)| **required)
+        .map(|(name, _)| Ident::new(&format!("__{}", name), Span::call_site()))
+        .collect::<Vec<_>>();
+
+    let mut errors = Vec::new();
+    let mut builder_fields = Vec::new();
+    let mut setters = Vec::new();
+    let mut build_fields = Vec::new();
+    let mut state = 0;
+
+    for (field, name) in fields.iter().zip(&names) {
+        let value_ty = match field.value_ty() {
+            Ok(ty) => ty,
+            Err(err) => {
+                errors.push(err);
+                continue;
+            }
+        };
+
+        let (value_param, value_arg, value) = if field.into {
+            (
+                quote!(<__V: Into<#value_ty>>),
+                quote!(__V),
+                quote!(value.into()),
+            )
+        } else {
+            (quote!(), quote!(#value_ty), quote!(value))
+        };
+
+        if field.is_required() {
+            let state_ty = &states[state];
+            builder_fields.push(quote!(#name: #state_ty));
+
+            let other_states = states
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != state)
+                .map(|(_, ty)| ty);
+            let unset = states.iter().enumerate().map(|(i, ty)| {
+                if i == state {
+                    quote!(())
+                } else {
+                    quote!(#ty)
+                }
+            });
+            let set = states.iter().enumerate().map(|(i, ty)| {
+                if i == state {
+                    let field_ty = &field.ty;
+                    quote!((#field_ty,))
+                } else {
+                    quote!(#ty)
+                }
+            });
+            let moved = names.iter().map(|other| {
+                if other == name {
+                    quote!(#other: (#value,))
+                } else {
+                    quote!(#other: self.#other)
+                }
+            });
+
+            setters.push(quote!(
+                #[allow(non_camel_case_types)]
+                impl<#(#params,)* #(#other_states),*> #builder<#(#args,)* #(#unset),*> #where_clause {
+                    #vis fn #name #value_param(self, value: #value_arg) -> #builder<#(#args,)* #(#set),*> {
+                        #builder { #(#moved,)* __marker: self.__marker }
+                    }
+                }
+            ));
+            build_fields.push(quote!(#name: self.#name.0));
+            state += 1;
+        } else {
+            let field_ty = &field.ty;
+            let (builder_ty, init) = if field.optional {
+                (quote!(#field_ty), quote!(self.#name))
+            } else {
+                let init = match field.default {
+                    Some(PropDefault::Expr(ref expr)) => {
+                        quote!(self.#name.unwrap_or_else(|| #expr))
+                    }
+                    _ => quote!(self.#name.unwrap_or_default()),
+                };
+                (quote!(Option<#field_ty>), init)
+            };
+            builder_fields.push(quote!(#name: #builder_ty));
+
+            setters.push(quote!(
+                #[allow(non_camel_case_types)]
+                impl<#(#params,)* #(#states),*> #builder<#(#args,)* #(#states),*> #where_clause {
+                    #vis fn #name #value_param(mut self, value: #value_arg) -> Self {
+                        self.#name = Some(#value);
+                        self
+                    }
+                }
+            ));
+            build_fields.push(quote!(#name: #init));
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(darling::Error::multiple(errors));
+    }
+
+    let unset = states.iter().map(|_| quote!(()));
+    let init = names.iter().zip(&required).map(|(name, required)| {
+        if *required {
+            quote!(#name: ())
+        } else {
+            quote!(#name: None)
+        }
+    });
+    let set = fields
+        .iter()
+        .filter(|field| field.is_required())
+        .map(|field| {
+            let ty = &field.ty;
+            quote!((#ty,))
+        });
+
+    Ok(quote!(
+        #[allow(non_camel_case_types)]
+        #vis struct #builder<#(#params,)* #(#states),*> #where_clause {
+            #(#builder_fields,)*
+            __marker: std::marker::PhantomData<(#(#markers,)*)>,
+        }
+
+        impl<#(#params),*> #ident #ty_generics #where_clause {
+            /// Create a builder for the widget.
+            #vis fn builder() -> #builder<#(#args,)* #(#unset),*> {
+                #builder { #(#init,)* __marker: std::marker::PhantomData }
+            }
+        }
+
+        #(#setters)*
+
+        impl<#(#params),*> #builder<#(#args,)* #(#set),*> #where_clause {
+            /// Create the widget, only available after all required props are set.
+            #vis fn build(self) -> #ident #ty_generics {
+                #ident { #(#build_fields),* }
+            }
+        }
+    ))
+}
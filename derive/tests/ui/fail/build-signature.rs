@@ -0,0 +1,14 @@
+use fehui::{Build, Layout, Layouter, Widget};
+use fehui_derive::Widget;
+
+#[derive(Debug, Widget)]
+#[widget(build = "render")]
+struct App;
+
+impl App {
+    fn render(&self) -> Option<App> {
+        None
+    }
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use fehui::{Build, Layout, Layouter, Widget};
+use fehui_derive::Widget;
+
+#[derive(Debug, Widget)]
+#[widget(build, bound = "T: +")]
+struct App<T>(T);
+
+impl<T> App<T> {
+    fn build(&self, _: &mut Build) -> Option<App<T>> {
+        None
+    }
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use fehui::{Build, Layout, Layouter, Widget};
+use fehui_derive::Widget;
+
+#[derive(Debug, Widget)]
+#[widget(build = "render view")]
+struct App;
+
+fn main() {}
@@ -0,0 +1,14 @@
+use fehui::{Build, Layout, Layouter, Widget};
+use fehui_derive::Widget;
+
+#[derive(Debug, Widget)]
+#[widget(state)]
+struct App;
+
+impl App {
+    fn state(&self) -> usize {
+        0
+    }
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use fehui_derive::Props;
+
+#[derive(Debug, Props)]
+struct Button {
+    label: String,
+    width: f32,
+}
+
+fn main() {
+    let _ = Button::builder().label("ok".to_string()).build();
+}
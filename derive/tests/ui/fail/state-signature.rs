@@ -0,0 +1,18 @@
+use fehui::{Build, Layout, Layouter, Widget};
+use fehui_derive::Widget;
+
+#[derive(Debug, Widget)]
+#[widget(build, state)]
+struct App;
+
+impl App {
+    fn build(&self, _: &usize, _: &mut Build) -> Option<App> {
+        None
+    }
+
+    fn state(&self, initial: usize) -> usize {
+        initial
+    }
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use fehui::{Build, Layout, Layouter, Widget};
+use fehui_derive::Widget;
+
+#[derive(Debug, Widget)]
+#[widget(build, memoize)]
+struct App;
+
+impl App {
+    fn build(&self, _: &mut Build) -> Option<App> {
+        None
+    }
+}
+
+fn main() {}
@@ -0,0 +1,51 @@
+use fehui::{Build, Children, Layout, Layouter, Widget};
+use fehui_derive::Widget;
+
+// Generated bounds extend the declared ones: `T: Widget + Clone + 'static + Debug + PartialEq`.
+#[derive(Debug, PartialEq, Widget)]
+#[widget(build, memo)]
+struct Row<T: Widget + Clone> {
+    items: Vec<T>,
+}
+
+impl<T: Widget + Clone> Row<T> {
+    fn build(&self, _: &mut Build) -> Children<Vec<T>> {
+        Children(self.items.clone())
+    }
+}
+
+// Lifetimes are required to be `'static`.
+#[derive(Debug, Clone, PartialEq, Widget)]
+#[widget(build)]
+struct Label<'a> {
+    text: &'a str,
+}
+
+impl<'a> Label<'a> {
+    fn build(&self, _: &mut Build) -> Option<Label<'a>> {
+        None
+    }
+}
+
+// The generated bounds wouldn't satisfy the `build` method.
+#[derive(Debug, Widget)]
+#[widget(build, bound = "T: Widget + Clone")]
+struct Wrapper<T> {
+    child: T,
+}
+
+impl<T: Widget + Clone> Wrapper<T> {
+    fn build(&self, _: &mut Build) -> T {
+        self.child.clone()
+    }
+}
+
+fn main() {
+    let mut ui = fehui::FehUI::new();
+    ui.build(Row {
+        items: vec![Label { text: "a" }, Label { text: "b" }],
+    });
+    ui.build(Wrapper {
+        child: Label { text: "c" },
+    });
+}
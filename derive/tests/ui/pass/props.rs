@@ -0,0 +1,23 @@
+use fehui_derive::Props;
+
+#[derive(Debug, Props)]
+struct Label<'a, T> {
+    text: &'a str,
+    value: T,
+    #[prop(default = "1.0")]
+    scale: f32,
+    #[prop(optional, into)]
+    tooltip: Option<String>,
+}
+
+fn main() {
+    let label = Label::builder()
+        .value(3)
+        .tooltip("count")
+        .text("items")
+        .build();
+    assert_eq!(label.text, "items");
+    assert_eq!(label.value, 3);
+    assert_eq!(label.scale, 1.0);
+    assert_eq!(label.tooltip.as_ref().map(String::as_str), Some("count"));
+}
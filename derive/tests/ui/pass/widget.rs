@@ -0,0 +1,50 @@
+use fehui::widgets::Row;
+use fehui::{views, Build, Event, EventCtxt, Layout, Layouter, StateSlot, Widget};
+use fehui_derive::Widget;
+
+struct CounterState {
+    count: usize,
+}
+
+#[derive(Debug, PartialEq, Widget)]
+#[widget(build = "render", state = "init", layout = "place", event = "on_event", memo)]
+struct Counter {
+    start: usize,
+}
+
+impl Counter {
+    fn render(&self, state: &CounterState, _: &mut Build) -> Row {
+        Row {
+            children: views![Label { count: state.count }],
+        }
+    }
+
+    fn init(&self) -> CounterState {
+        CounterState { count: self.start }
+    }
+
+    fn place(&self, _: Layouter) -> Layout {
+        Layout::Pass
+    }
+
+    fn on_event(&self, ctxt: &mut EventCtxt, _: &Event) -> bool {
+        ctxt.state(StateSlot::Index(0)).is_some()
+    }
+}
+
+#[derive(Debug, Widget)]
+#[widget(build)]
+struct Label {
+    count: usize,
+}
+
+impl Label {
+    fn build(&self, _: &mut Build) -> Option<Row> {
+        None
+    }
+}
+
+fn main() {
+    let mut ui = fehui::FehUI::new();
+    ui.build(Counter { start: 1 });
+}
@@ -1,10 +1,22 @@
+use fehui_hotload::{Hotload, Target};
+use std::time::Duration;
+
 fn main() {
+    let mut hotload = Hotload::cargo(".", Target::Example("hotload-app".into())).unwrap();
+    // Dropped before the hotloader, which owns the code of its widgets and effects.
     let mut ui = fehui::FehUI::new();
 
-    let mut hotload = fehui_hotload::Hotload::new("target/debug/examples", "hotload-app").unwrap(); // todo: release support
-    hotload.reload(&mut ui);
+    hotload.on_reload(|event| match event.result {
+        Ok(()) => println!("reloaded {:?} in {:?}", event.path, event.duration),
+        Err(ref err) => println!("reload failed: {}", err),
+    });
+
+    if hotload.reload(&mut ui).is_err() {
+        // No library built yet.
+        hotload.build();
+    }
 
     loop {
-        hotload.try_reload(&mut ui);
+        let _ = hotload.wait_for_reload(&mut ui, Duration::from_millis(100));
     }
 }
@@ -1,11 +1,91 @@
+use std::any::Any;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Version of the hook protocol between host and library.
+pub const API_VERSION: u32 = 3;
+
+/// Version information exported by libraries via `fehui_hook!`.
+///
+/// Host and library need to agree on both fields, otherwise loading the library
+/// would result in undefined behavior.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ApiVersion {
+    pub api: u32,
+    /// Hash of the fehui and fehui-hotload versions, compiler and `FehUI` layout.
+    pub fingerprint: u64,
+}
+
+impl std::fmt::Display for ApiVersion {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "api {} ({:016x})", self.api, self.fingerprint)
+    }
+}
+
+/// Version information of the current compilation.
+pub fn api_version() -> ApiVersion {
+    // The type id of `FehUI` changes with the crate version, features and compiler.
+    let mut hasher = DefaultHasher::new();
+    fehui::VERSION.hash(&mut hasher);
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    std::any::TypeId::of::<fehui::FehUI>().hash(&mut hasher);
+    std::mem::size_of::<fehui::FehUI>().hash(&mut hasher);
+    std::mem::align_of::<fehui::FehUI>().hash(&mut hasher);
+
+    ApiVersion {
+        api: API_VERSION,
+        fingerprint: hasher.finish(),
+    }
+}
+
+/// Return codes of the functions exported by libraries.
+pub const CALL_OK: u32 = 0;
+pub const CALL_PANICKED: u32 = 1;
+
+/// Run a library callback, catching panics as these must not unwind into the host.
+///
+/// The panic message will be written to `message`.
+#[doc(hidden)]
+pub fn catch_panic<F: FnOnce()>(f: F, message: &mut String) -> u32 {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(()) => CALL_OK,
+        Err(payload) => {
+            *message = panic_message(&*payload);
+            CALL_PANICKED
+        }
+    }
+}
+
+fn panic_message(payload: &(Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Export a load function from a hot reloaded library.
+///
+/// Shorthand for a `Plugin` only implementing `on_load`, see `fehui_plugin!`.
 #[macro_export]
 macro_rules! fehui_hook {
     ($load:expr) => {
-        #[no_mangle]
-        extern "C" fn fehui_load(ui: *mut fehui::FehUI) {
-            println!("fehui :: load");
-            let ui = unsafe { &mut *ui };
-            $load(ui);
+        $crate::fehui_hook!($load, || ());
+    };
+    ($load:expr, $unload:expr) => {
+        #[derive(Default)]
+        struct FehuiHook;
+
+        impl $crate::Plugin for FehuiHook {
+            fn on_load(&mut self, ui: &mut fehui::FehUI) {
+                $load(ui);
+            }
         }
+
+        $crate::fehui_plugin!(FehuiHook, (), $unload);
     };
 }
@@ -0,0 +1,81 @@
+use crate::Error;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory of assets, named by their path relative to the directory.
+pub(crate) struct AssetDir {
+    pub dir: PathBuf,
+    pending: HashSet<PathBuf>, // changed since the last update
+}
+
+impl AssetDir {
+    /// All existing assets will be loaded on the first update.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.as_ref().canonicalize()?;
+        let mut pending = HashSet::new();
+        scan(&dir, &mut pending)?;
+        Ok(AssetDir { dir, pending })
+    }
+
+    /// Mark the file as changed, returns `false` if it's not part of the directory.
+    #[cfg_attr(not(feature = "dynamic"), allow(dead_code))]
+    pub fn changed(&mut self, path: &Path) -> bool {
+        if !path.starts_with(&self.dir) {
+            return false;
+        }
+        if !path.is_dir() {
+            self.pending.insert(path.to_path_buf());
+        }
+        true
+    }
+
+    /// Apply pending changes to the UI, returns `true` if any asset changed.
+    ///
+    /// Assets which failed to load stay pending.
+    pub fn update(&mut self, ui: &mut fehui::FehUI) -> Result<bool, Error> {
+        if self.pending.is_empty() {
+            return Ok(false);
+        }
+
+        let mut result = Ok(true);
+        let pending = std::mem::replace(&mut self.pending, HashSet::new());
+        for path in pending {
+            let name = match path.strip_prefix(&self.dir) {
+                Ok(name) => asset_name(name),
+                Err(_) => continue,
+            };
+            match fs::read(&path) {
+                Ok(data) => ui.set_asset(name, data),
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => ui.remove_asset(&name),
+                Err(err) => {
+                    // Retried on the next update, e.g. the file might still be written.
+                    self.pending.insert(path);
+                    result = Err(err.into());
+                }
+            }
+        }
+        result
+    }
+}
+
+// Names use `/` as separator independent of the platform.
+fn asset_name(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn scan(dir: &Path, files: &mut HashSet<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            scan(&path, files)?;
+        } else {
+            files.insert(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,251 @@
+use crate::entry;
+use crate::{Diagnostic, Error, Target};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+
+fn cargo_bin() -> std::ffi::OsString {
+    std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into())
+}
+
+fn cargo_error<E: std::fmt::Display>(err: E) -> Error {
+    Error::Cargo(err.to_string())
+}
+
+/// Background cargo builds of a crate.
+pub struct Cargo {
+    manifest_dir: PathBuf,
+    target: Target,
+    release: bool,
+    build: Option<Receiver<Result<PathBuf, Error>>>,
+    outdated: bool, // sources changed during the running build
+}
+
+impl Cargo {
+    pub fn new<P: AsRef<Path>>(manifest_dir: P, target: Target, release: bool) -> Self {
+        Cargo {
+            manifest_dir: manifest_dir.as_ref().to_path_buf(),
+            target,
+            release,
+            build: None,
+            outdated: false,
+        }
+    }
+
+    /// Source directories which need to be watched for changes.
+    pub fn source_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = vec![self.manifest_dir.join("src")];
+        if let Target::Example(_) = self.target {
+            dirs.push(self.manifest_dir.join("examples"));
+        }
+        dirs.into_iter().filter(|dir| dir.is_dir()).collect()
+    }
+
+    /// Path of the library produced by the build, resolved via `cargo metadata`.
+    pub fn output_path(&self) -> Result<PathBuf, Error> {
+        let output = Command::new(cargo_bin())
+            .args(&["metadata", "--format-version", "1", "--no-deps"])
+            .current_dir(&self.manifest_dir)
+            .output()?;
+        if !output.status.success() {
+            return Err(cargo_error(String::from_utf8_lossy(&output.stderr)));
+        }
+
+        let metadata: serde_json::Value =
+            serde_json::from_slice(&output.stdout).map_err(cargo_error)?;
+        self.resolve_output(&metadata)
+    }
+
+    // Library path of the target within the `cargo metadata` output.
+    fn resolve_output(&self, metadata: &serde_json::Value) -> Result<PathBuf, Error> {
+        let target_dir = metadata["target_directory"]
+            .as_str()
+            .ok_or_else(|| cargo_error("missing target directory"))?;
+
+        let mut path = Path::new(target_dir).join(if self.release { "release" } else { "debug" });
+        let name = match self.target {
+            Target::Example(ref name) => {
+                path.push("examples");
+                // Dashes are replaced in library file names, same as for `Target::Lib`.
+                name.replace('-', "_")
+            }
+            Target::Lib => {
+                let manifest = self.manifest_dir.join("Cargo.toml").canonicalize()?;
+                let package = metadata["packages"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .find(|package| {
+                        package["manifest_path"]
+                            .as_str()
+                            .map_or(false, |path| Path::new(path) == manifest)
+                    })
+                    .ok_or_else(|| cargo_error("package not found in cargo metadata"))?;
+                let lib = package["targets"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .find(|target| is_dylib(target))
+                    .ok_or_else(|| cargo_error("package has no dylib target"))?;
+                lib["name"].as_str().unwrap_or_default().replace('-', "_")
+            }
+        };
+
+        Ok(path.join(entry::dylib_name(&name)))
+    }
+
+    pub fn is_building(&self) -> bool {
+        self.build.is_some()
+    }
+
+    /// Start a new build in the background.
+    ///
+    /// If a build is already running, a new one will be started after it finished.
+    pub fn build(&mut self) {
+        if self.build.is_some() {
+            self.outdated = true;
+            return;
+        }
+
+        let mut command = Command::new(cargo_bin());
+        command
+            .args(&["build", "--message-format=json"])
+            .current_dir(&self.manifest_dir);
+        match self.target {
+            Target::Lib => command.arg("--lib"),
+            Target::Example(ref name) => command.args(&["--example", name]),
+        };
+        if self.release {
+            command.arg("--release");
+        }
+
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            let _ = tx.send(run_build(command));
+        });
+        self.build = Some(rx);
+    }
+
+    /// Check for a finished build, returning the path of the built library on success.
+    pub fn poll(&mut self) -> Option<Result<PathBuf, Error>> {
+        let result = match self.build {
+            Some(ref rx) => match rx.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => Err(cargo_error("build thread terminated")),
+            },
+            None => return None,
+        };
+        self.build = None;
+
+        if self.outdated {
+            self.outdated = false;
+            self.build();
+            return None;
+        }
+
+        Some(result)
+    }
+}
+
+fn is_dylib(target: &serde_json::Value) -> bool {
+    target["kind"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .any(|kind| kind == "dylib" || kind == "cdylib")
+}
+
+fn run_build(mut command: Command) -> Result<PathBuf, Error> {
+    let output = command.output()?;
+
+    let mut diagnostics = Vec::new();
+    let mut artifact = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let message: serde_json::Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(_) => continue,
+        };
+
+        match message["reason"].as_str() {
+            Some("compiler-message") => {
+                let message = &message["message"];
+                diagnostics.push(Diagnostic {
+                    level: message["level"].as_str().unwrap_or_default().to_string(),
+                    message: message["message"].as_str().unwrap_or_default().to_string(),
+                    rendered: message["rendered"].as_str().map(String::from),
+                });
+            }
+            Some("compiler-artifact") if is_dylib(&message["target"]) => {
+                let dylib = message["filenames"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|file| file.as_str())
+                    .find(|file| {
+                        Path::new(file).extension()
+                            == Some(std::env::consts::DLL_EXTENSION.as_ref())
+                    });
+                if let Some(dylib) = dylib {
+                    artifact = Some(PathBuf::from(dylib));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if !output.status.success() {
+        return Err(Error::Build(diagnostics));
+    }
+
+    artifact.ok_or_else(|| cargo_error("build didn't produce a library"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn metadata(lib_name: &str, manifest_path: &Path) -> serde_json::Value {
+        json!({
+            "target_directory": "/target",
+            "packages": [{
+                "manifest_path": manifest_path,
+                "targets": [
+                    { "name": "app", "kind": ["bin"] },
+                    { "name": lib_name, "kind": ["cdylib"] },
+                ],
+            }],
+        })
+    }
+
+    #[test]
+    fn example_output() {
+        let cargo = Cargo::new(".", Target::Example("hotload-app".into()), false);
+        let path = cargo
+            .resolve_output(&metadata("ui", Path::new("/none")))
+            .unwrap();
+        let expected = Path::new("/target/debug/examples").join(entry::dylib_name("hotload_app"));
+        assert_eq!(path, expected);
+    }
+
+    #[test]
+    fn lib_output() {
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+        let manifest = Path::new(manifest_dir)
+            .join("Cargo.toml")
+            .canonicalize()
+            .unwrap();
+        let cargo = Cargo::new(manifest_dir, Target::Lib, true);
+        let path = cargo.resolve_output(&metadata("my-ui", &manifest)).unwrap();
+        assert_eq!(
+            path,
+            Path::new("/target/release").join(entry::dylib_name("my_ui"))
+        );
+
+        // Other packages of the workspace are ignored.
+        let other = cargo.resolve_output(&metadata("my-ui", Path::new("/none")));
+        assert!(other.is_err());
+    }
+}
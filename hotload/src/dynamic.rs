@@ -0,0 +1,374 @@
+//! Hot reloading of dynamic libraries.
+
+use crate::assets::AssetDir;
+use crate::cargo;
+use crate::entry::{self, Entry};
+use crate::registry::Registry;
+use crate::{Error, Listeners, ReloadEvent, Target};
+use notify::Watcher;
+use std::any::Any;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Interval for polling running cargo builds while waiting for reloads.
+pub(crate) const BUILD_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Shared by all hotloaders, as libraries of several modules can be active in the same UI.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+fn next_generation() -> u64 {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Registration of a hotloaded library as module of a `ModuleHost`.
+pub(crate) struct Module {
+    pub name: String,
+    pub registry: Registry,
+}
+
+/// Loads and reloads a UI library.
+///
+/// The UI has to be dropped before the hotloader, as dropping it runs the remaining
+/// effect cleanups, which might be code of the loaded libraries.
+pub struct Hotload {
+    path: PathBuf,
+    watcher: notify::RecommendedWatcher,
+    rx: Receiver<notify::DebouncedEvent>,
+    entry: Option<entry::Entry>,
+    old_entries: Vec<(entry::Entry, u64)>, // still referenced by the UI, with retiring generation
+    cargo: Option<cargo::Cargo>,           // build on source changes
+    pub(crate) module: Option<Module>,
+    assets: Option<AssetDir>,
+    changed: bool,             // watched files changed since the last reload
+    generations: HashSet<u64>, // set by this hotloader, states of these belong to its libraries
+    listeners: Listeners,
+}
+
+impl Hotload {
+    pub fn new<P: AsRef<Path>>(path: P, lib_name: &str) -> Result<Self, Error> {
+        let (tx, rx) = channel();
+        let path = path.as_ref().join(entry::dylib_name(lib_name));
+        let mut watcher = notify::watcher(tx, Duration::from_secs(1))?;
+        watcher.watch(&path, notify::RecursiveMode::NonRecursive)?;
+
+        Ok(Hotload {
+            path,
+            watcher,
+            rx,
+            entry: None,
+            old_entries: Vec::new(),
+            cargo: None,
+            module: None,
+            assets: None,
+            changed: false,
+            generations: HashSet::new(),
+            listeners: Listeners::new(),
+        })
+    }
+
+    /// Watch the sources of a crate and rebuild the library via cargo on changes.
+    ///
+    /// The library path is resolved from the cargo metadata, release builds will be used
+    /// if the host has been built without debug assertions.
+    pub fn cargo<P: AsRef<Path>>(manifest_dir: P, target: Target) -> Result<Self, Error> {
+        let cargo = cargo::Cargo::new(manifest_dir, target, !cfg!(debug_assertions));
+        let path = cargo.output_path()?;
+
+        let (tx, rx) = channel();
+        let mut watcher = notify::watcher(tx, Duration::from_secs(1))?;
+        for dir in cargo.source_dirs() {
+            watcher.watch(&dir, notify::RecursiveMode::Recursive)?;
+        }
+
+        Ok(Hotload {
+            path,
+            watcher,
+            rx,
+            entry: None,
+            old_entries: Vec::new(),
+            cargo: Some(cargo),
+            module: None,
+            assets: None,
+            changed: false,
+            generations: HashSet::new(),
+            listeners: Listeners::new(),
+        })
+    }
+
+    /// Watch a directory of assets (e.g. stylesheets, images or fonts) and update these
+    /// in the UI on changes, without reloading the library.
+    ///
+    /// Assets are named by their path relative to `dir`, using `/` as separator.
+    /// Existing assets will be loaded on the next reload.
+    pub fn watch_assets<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), Error> {
+        let assets = AssetDir::new(dir)?;
+        if let Some(ref prev) = self.assets {
+            if prev.dir == assets.dir {
+                return Ok(());
+            }
+            // Unwatch first, the directories might overlap.
+            let _ = self.watcher.unwatch(&prev.dir);
+            self.assets = None;
+        }
+        self.watcher
+            .watch(&assets.dir, notify::RecursiveMode::Recursive)?;
+        self.assets = Some(assets);
+        Ok(())
+    }
+
+    /// Trigger a cargo build, only available for hotloaders created via `Hotload::cargo`.
+    pub fn build(&mut self) {
+        if let Some(ref mut cargo) = self.cargo {
+            cargo.build();
+        }
+    }
+
+    pub fn is_building(&self) -> bool {
+        self.cargo.as_ref().map_or(false, |cargo| cargo.is_building())
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.entry.is_some()
+    }
+
+    /// Name of the module if added to a `ModuleHost`.
+    pub fn module_name(&self) -> Option<&str> {
+        self.module.as_ref().map(|module| module.name.as_str())
+    }
+
+    /// Register a callback, which will be called after each reload attempt.
+    pub fn on_reload<F: FnMut(&ReloadEvent) + 'static>(&mut self, callback: F) {
+        self.listeners.add(callback);
+    }
+
+    /// Channel receiving an event after each reload attempt.
+    pub fn subscribe(&mut self) -> Receiver<ReloadEvent> {
+        self.listeners.subscribe()
+    }
+
+    fn dispatch(&mut self, duration: Duration, result: &Result<(), Error>) {
+        self.listeners.dispatch(&self.path, duration, result);
+    }
+
+    fn handle_event(&mut self, event: notify::DebouncedEvent) {
+        use notify::DebouncedEvent as Event;
+        if let Some(ref mut assets) = self.assets {
+            let is_asset = match event {
+                Event::Create(ref path) | Event::Write(ref path) | Event::Remove(ref path) => {
+                    assets.changed(path)
+                }
+                Event::Rename(ref from, ref to) => assets.changed(from) | assets.changed(to),
+                _ => false,
+            };
+            if is_asset {
+                return;
+            }
+        }
+
+        match event {
+            Event::Create(_) | Event::Write(_) => self.changed = true,
+            Event::Remove(_) | Event::Rename(..) if self.cargo.is_some() => self.changed = true,
+            _ => (),
+        }
+    }
+
+    pub fn try_reload(&mut self, ui: &mut fehui::FehUI) -> Result<bool, Error> {
+        self.unload_unused(ui);
+
+        while let Ok(event) = self.rx.try_recv() {
+            self.handle_event(event);
+        }
+        let mut reload = std::mem::replace(&mut self.changed, false);
+
+        // Errors are returned after handling the library, which is reloaded regardless.
+        let assets_updated = match self.assets {
+            Some(ref mut assets) => assets.update(ui),
+            None => Ok(false),
+        };
+
+        let mut result = Ok(());
+        if let Some(ref mut cargo) = self.cargo {
+            // Events refer to source files, the library needs to be rebuilt first.
+            if reload {
+                cargo.build();
+            }
+            reload = match cargo.poll() {
+                Some(Ok(path)) => {
+                    self.path = path;
+                    true
+                }
+                Some(Err(err)) => {
+                    result = Err(err);
+                    self.dispatch(Duration::default(), &result);
+                    false
+                }
+                None => false,
+            };
+        }
+
+        let reloaded = if reload {
+            self.reload(ui).map(|()| true)
+        } else {
+            result.map(|()| false)
+        };
+
+        // Loading the library builds the UI, otherwise assets only require a rebuild.
+        let rebuilt = match (&assets_updated, &reloaded) {
+            (Ok(true), Ok(false)) | (Ok(true), Err(_)) => ui.rebuild(),
+            _ => false,
+        };
+
+        let reloaded = reloaded?;
+        assets_updated?;
+        Ok(reloaded || rebuilt)
+    }
+
+    /// Block until the library has been reloaded, assets have been updated or the timeout elapsed.
+    ///
+    /// Returns `true` if the library has been reloaded or the UI has been rebuilt.
+    pub fn wait_for_reload(
+        &mut self,
+        ui: &mut fehui::FehUI,
+        timeout: Duration,
+    ) -> Result<bool, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.try_reload(ui)? {
+                return Ok(true);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+
+            // Running builds don't emit file events, these need to be polled.
+            let mut wait = deadline - now;
+            if self.is_building() && wait > BUILD_POLL_INTERVAL {
+                wait = BUILD_POLL_INTERVAL;
+            }
+            match self.rx.recv_timeout(wait) {
+                Ok(event) => self.handle_event(event),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => return Ok(false),
+            }
+        }
+    }
+
+    /// Load the library again, replacing the current one.
+    ///
+    /// On failure the current library will be kept active and the error is returned,
+    /// e.g. if the library is still being written by cargo.
+    pub fn reload(&mut self, ui: &mut fehui::FehUI) -> Result<(), Error> {
+        let start = Instant::now();
+        let result = self.load(ui);
+        self.dispatch(start.elapsed(), &result);
+        result
+    }
+
+    fn load(&mut self, ui: &mut fehui::FehUI) -> Result<(), Error> {
+        // Load and validate the new library before touching the current one.
+        let generation = next_generation();
+        let mut entry = Entry::new(&self.path, generation)?;
+        // The library builds the UI on load, no separate rebuild needed.
+        // Asset errors are returned after loading, the library is loaded regardless.
+        let assets_updated = match self.assets {
+            Some(ref mut assets) => assets.update(ui).map(|_| ()),
+            None => Ok(()),
+        };
+
+        // States need to be serialized before switching to the code of the new library,
+        // as the state types might have changed.
+        if let Some(ref mut prev) = self.entry {
+            // The previous library will be replaced regardless of failures during unloading.
+            let _ = prev.unload(ui);
+        }
+        // Only states of this library, other modules of a `ModuleHost` keep theirs.
+        self.generations.insert(generation);
+        let states = ui.save_generations(&self.generations);
+        ui.restore_states(states.clone());
+        ui.set_generation(generation);
+        if let Err(err) = self.register(&mut entry).and_then(|()| entry.load(ui)) {
+            // Keep running the previous library. The failed library can't be unloaded
+            // as the partially built UI might still reference it.
+            let retired = next_generation();
+            self.generations.insert(retired);
+            ui.set_generation(retired);
+            self.unregister();
+            self.old_entries.push((entry, retired));
+            if let Some(mut prev) = self.entry.take() {
+                ui.save_generations(&self.generations);
+                ui.restore_states(states);
+                let _ = self.register(&mut prev);
+                let _ = prev.load(ui);
+                self.entry = Some(prev);
+            }
+            return Err(err);
+        }
+
+        if let Some(prev) = self.entry.replace(entry) {
+            self.old_entries.push((prev, generation));
+        }
+        self.unload_unused(ui);
+        assets_updated
+    }
+
+    // Replace the registered widgets of the module by the ones of the entry.
+    fn register(&mut self, entry: &mut Entry) -> Result<(), Error> {
+        self.unregister();
+        match self.module {
+            Some(ref module) => {
+                module.registry.set_module(&module.name, entry.generation);
+                entry.register(&module.registry)
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Factories need to be removed before the library gets unloaded.
+    fn unregister(&mut self) {
+        if let Some(ref module) = self.module {
+            module.registry.remove_module(&module.name);
+        }
+    }
+
+    /// Run the frame callback of the current library.
+    pub fn frame(&mut self, ui: &mut fehui::FehUI) -> Result<(), Error> {
+        match self.entry {
+            Some(ref mut entry) => entry.frame(ui),
+            None => Ok(()),
+        }
+    }
+
+    /// Forward a host event (e.g. a window event) to the current library.
+    pub fn event<E: Any>(&mut self, ui: &mut fehui::FehUI, event: &E) -> Result<(), Error> {
+        match self.entry {
+            Some(ref mut entry) => entry.event(ui, event),
+            None => Ok(()),
+        }
+    }
+
+    /// Unload old libraries, which aren't referenced by any widget or state of the UI anymore.
+    pub fn unload_unused(&mut self, ui: &fehui::FehUI) {
+        if self.old_entries.is_empty() {
+            return;
+        }
+
+        // Widgets and states created while a library was active may reference its code.
+        let in_use = ui.generations_in_use();
+        self.old_entries.retain(|(entry, retired)| {
+            in_use
+                .iter()
+                .any(|generation| (entry.generation..*retired).contains(generation))
+        });
+    }
+}
+
+impl Drop for Hotload {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
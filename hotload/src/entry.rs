@@ -1,5 +1,10 @@
 use std::path::Path;
 use std::fs;
+use crate::api::{api_version, ApiVersion, CALL_OK};
+use crate::plugin::{event_type, Callback, PluginVTable};
+use std::any::Any;
+use std::os::raw::c_void;
+use crate::registry::Registry;
 use crate::Error;
 
 #[cfg(windows)]
@@ -21,31 +26,106 @@ pub fn dylib_name(name: &str) -> String {
 }
 
 pub struct Entry {
+    pub generation: u64, // first code generation of widgets and states created by the library
     _library: libloading::Library,
-    api_load: Symbol<extern "C" fn(ui: *mut fehui::FehUI)>,
+    vtable: PluginVTable,
+    plugin: *mut c_void,
+    api_unload: Symbol<extern "C" fn()>,
     _dir: tempfile::TempDir, // WARNING: this should be dropped last
 }
 
 impl Entry {
-    pub fn new<P: AsRef<Path>>(lib: P) -> Result<Self, Error> {
+    pub fn new<P: AsRef<Path>>(lib: P, generation: u64) -> Result<Self, Error> {
         let dir = tempfile::tempdir()?;
         let lib_name = lib.as_ref().file_name().unwrap();
         let tmp_lib = dir.path().join(lib_name);
         fs::copy(lib.as_ref(), tmp_lib)?;
         let library = libloading::Library::new(dir.path().join(lib_name))?;
-        let api_load = unsafe {
+
+        // Verify that the library has been built against a compatible fehui version.
+        let expected = api_version();
+        let found = unsafe {
+            library
+                .get::<extern "C" fn() -> ApiVersion>(b"fehui_version")
+                .ok()
+                .map(|version| version())
+        };
+        if found != Some(expected) {
+            return Err(Error::Version { expected, found });
+        }
+
+        let vtable = unsafe { library.get::<extern "C" fn() -> PluginVTable>(b"fehui_plugin")?() };
+        let api_unload = unsafe {
             library
-                .get::<extern "C" fn(*mut fehui::FehUI)>(b"fehui_load")?
+                .get::<extern "C" fn()>(b"fehui_unload")?
                 .into_raw()
         };
+
+        let plugin = (vtable.create)();
+        if plugin.is_null() {
+            return Err(Error::Panicked("plugin creation panicked".into()));
+        }
+
         Ok(Entry {
+            generation,
             _library: library,
-            api_load,
+            vtable,
+            plugin,
+            api_unload,
             _dir: dir,
         })
     }
 
-    pub fn load(&mut self, ui: &mut fehui::FehUI) {
-        (self.api_load)(ui as *mut _)
+    fn call(&mut self, ui: &mut fehui::FehUI, callback: Callback) -> Result<(), Error> {
+        let mut message = String::new();
+        match callback(self.plugin, ui as *mut _, &mut message as *mut _) {
+            CALL_OK => Ok(()),
+            _ => Err(Error::Panicked(message)),
+        }
     }
-}
\ No newline at end of file
+
+    pub fn register(&mut self, registry: &Registry) -> Result<(), Error> {
+        let mut message = String::new();
+        match (self.vtable.on_register)(self.plugin, registry as *const _, &mut message as *mut _) {
+            CALL_OK => Ok(()),
+            _ => Err(Error::Panicked(message)),
+        }
+    }
+
+    pub fn load(&mut self, ui: &mut fehui::FehUI) -> Result<(), Error> {
+        let on_load = self.vtable.on_load;
+        self.call(ui, on_load)
+    }
+
+    pub fn unload(&mut self, ui: &mut fehui::FehUI) -> Result<(), Error> {
+        let on_unload = self.vtable.on_unload;
+        self.call(ui, on_unload)
+    }
+
+    pub fn frame(&mut self, ui: &mut fehui::FehUI) -> Result<(), Error> {
+        let on_frame = self.vtable.on_frame;
+        self.call(ui, on_frame)
+    }
+
+    /// Forward an event to the plugin, events of other types than expected by the plugin are ignored.
+    pub fn event<E: Any>(&mut self, ui: &mut fehui::FehUI, event: &E) -> Result<(), Error> {
+        if self.vtable.event_type != event_type::<E>() {
+            return Ok(());
+        }
+
+        let mut message = String::new();
+        let event = event as *const E as *const c_void;
+        match (self.vtable.on_event)(self.plugin, ui as *mut _, event, &mut message as *mut _) {
+            CALL_OK => Ok(()),
+            _ => Err(Error::Panicked(message)),
+        }
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        (self.vtable.destroy)(self.plugin);
+        // Allow the library to release its resources before unloading.
+        (self.api_unload)()
+    }
+}
@@ -1,19 +1,56 @@
+#[cfg(not(any(feature = "dynamic", feature = "static")))]
+compile_error!("either the `dynamic` or `static` feature needs to be enabled");
+#[cfg(all(feature = "dynamic", feature = "static"))]
+compile_error!("the `dynamic` and `static` features are exclusive, disable the default features");
 
-
-mod entry;
 mod api;
+mod assets;
+mod plugin;
+mod registry;
+
+#[cfg(feature = "dynamic")]
+mod cargo;
+#[cfg(feature = "dynamic")]
+mod dynamic;
+#[cfg(feature = "dynamic")]
+mod entry;
+#[cfg(feature = "dynamic")]
+mod modules;
+#[cfg(feature = "static")]
+mod static_link;
 
-use notify::Watcher;
 use std::{error, fmt, io};
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::{Receiver, channel};
+use std::sync::mpsc::{Receiver, Sender, channel};
 use std::time::Duration;
-use entry::Entry;
+
+#[cfg(feature = "dynamic")]
+pub use dynamic::Hotload;
+#[cfg(feature = "dynamic")]
+pub use modules::ModuleHost;
+#[cfg(feature = "static")]
+pub use static_link::Hotload;
+
+pub use api::{api_version, catch_panic, ApiVersion};
+pub use plugin::{event_type, plugin_vtable, Plugin, PluginVTable};
+pub use registry::Registry;
 
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
+    #[cfg(feature = "dynamic")]
     Notify(notify::Error),
+    /// Library has been built against an incompatible version or doesn't export one.
+    Version {
+        expected: ApiVersion,
+        found: Option<ApiVersion>,
+    },
+    /// Load hook of the library panicked.
+    Panicked(String),
+    /// Cargo build failed with the contained compiler messages.
+    Build(Vec<Diagnostic>),
+    /// Invoking cargo or parsing its output failed.
+    Cargo(String),
 }
 
 impl From<io::Error> for Error {
@@ -22,6 +59,7 @@ impl From<io::Error> for Error {
     }
 }
 
+#[cfg(feature = "dynamic")]
 impl From<notify::Error> for Error {
     fn from(err: notify::Error) -> Error {
         Error::Notify(err)
@@ -32,15 +70,25 @@ impl error::Error for Error {
     fn description(&self) -> &str {
         match *self {
             Error::Io(ref err) => err.description(),
+            #[cfg(feature = "dynamic")]
             Error::Notify(ref notify) => notify.description(),
+            Error::Version { .. } => "incompatible library version",
+            Error::Panicked(_) => "library panicked",
+            Error::Build(_) => "cargo build failed",
+            Error::Cargo(ref err) => err.as_str(),
         }
     }
 
     fn cause(&self) -> Option<&error::Error> {
-        Some(match *self {
-            Error::Io(ref err) => err,
-            Error::Notify(ref notify) => notify,
-        })
+        match *self {
+            Error::Io(ref err) => Some(err),
+            #[cfg(feature = "dynamic")]
+            Error::Notify(ref notify) => Some(notify),
+            Error::Version { .. }
+            | Error::Panicked(_)
+            | Error::Build(_)
+            | Error::Cargo(_) => None,
+        }
     }
 }
 
@@ -48,66 +96,95 @@ impl fmt::Display for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Error::Io(ref err) => err.fmt(fmt),
+            #[cfg(feature = "dynamic")]
             Error::Notify(ref err) => err.fmt(fmt),
+            Error::Version {
+                ref expected,
+                found: Some(ref found),
+            } => write!(
+                fmt,
+                "incompatible library version: expected {}, found {}",
+                expected, found
+            ),
+            Error::Version { found: None, .. } => {
+                write!(fmt, "library doesn't export a fehui version")
+            }
+            Error::Panicked(ref message) => write!(fmt, "library panicked: {}", message),
+            Error::Build(ref diagnostics) => {
+                writeln!(fmt, "cargo build failed")?;
+                for diagnostic in diagnostics {
+                    match diagnostic.rendered {
+                        Some(ref rendered) => write!(fmt, "{}", rendered)?,
+                        None => writeln!(fmt, "{}: {}", diagnostic.level, diagnostic.message)?,
+                    }
+                }
+                Ok(())
+            }
+            Error::Cargo(ref err) => write!(fmt, "cargo: {}", err),
         }
     }
 }
 
-pub struct Hotload {
-    path: PathBuf,
-    _watcher: notify::RecommendedWatcher,
-    rx: Receiver<notify::DebouncedEvent>,
-    entry: Option<entry::Entry>,
-    old_entries: Vec<entry::Entry>, // needed for sth like dropping
+/// Cargo target producing the hot reloaded library.
+#[derive(Clone, Debug)]
+pub enum Target {
+    Lib,
+    Example(String),
 }
 
-impl Hotload {
-    pub fn new<P: AsRef<Path>>(path: P, lib_name: &str) -> Result<Self, Error> {
-        let (tx, rx) = channel();
-        let path = path.as_ref().join(entry::dylib_name(lib_name));
-        let mut watcher = notify::watcher(tx, Duration::from_secs(1))?;
-        watcher.watch(&path, notify::RecursiveMode::NonRecursive)?;
-
-
-        Ok(Hotload {
-            path,
-            _watcher: watcher,
-            rx,
-            entry: None,
-            old_entries: Vec::new(),
-        })
-    }
+/// Compiler message emitted during a cargo build.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub level: String,
+    pub message: String,
+    /// Human readable representation as printed by rustc.
+    pub rendered: Option<String>,
+}
 
-    pub fn is_loaded(&self) -> bool {
-        self.entry.is_some()
-    }
+/// Emitted after each reload attempt.
+#[derive(Clone, Debug)]
+pub struct ReloadEvent {
+    pub path: PathBuf,
+    /// Time spent for loading the library.
+    pub duration: Duration,
+    /// Error message if the reload (or the preceding cargo build) failed.
+    pub result: Result<(), String>,
+}
 
-    pub fn try_reload(&mut self, ui: &mut fehui::FehUI) -> Result<bool, Error> {
-        let mut reload = false;
-        while let Ok(event) = self.rx.try_recv() {
-            use notify::DebouncedEvent as Event;
-            match event {
-                Event::Create(_) | Event::Write(_) => reload = true,
-                _ => (),
-            }
-        }
+// Reload event callbacks and subscribers.
+struct Listeners {
+    callbacks: Vec<Box<FnMut(&ReloadEvent)>>,
+    subscribers: Vec<Sender<ReloadEvent>>,
+}
 
-        if reload {
-            self.reload(ui)?;
-            Ok(true)
-        } else {
-            Ok(false)
+impl Listeners {
+    fn new() -> Self {
+        Listeners {
+            callbacks: Vec::new(),
+            subscribers: Vec::new(),
         }
     }
 
-    pub fn reload(&mut self, ui: &mut fehui::FehUI) -> Result<(), Error> {
-        if let Some(entry) = self.entry.take() {
-            self.old_entries.push(entry);
-        }
-        self.entry = Some(Entry::new(&self.path)?);
-        if let Some(ref mut entry) = &mut self.entry {
-            entry.load(ui);
+    fn add<F: FnMut(&ReloadEvent) + 'static>(&mut self, callback: F) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    fn subscribe(&mut self) -> Receiver<ReloadEvent> {
+        let (tx, rx) = channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    fn dispatch(&mut self, path: &Path, duration: Duration, result: &Result<(), Error>) {
+        let event = ReloadEvent {
+            path: path.to_path_buf(),
+            duration,
+            result: result.as_ref().map(|_| ()).map_err(|err| err.to_string()),
+        };
+        for callback in &mut self.callbacks {
+            callback(&event);
         }
-        Ok(())
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,132 @@
+//! Hosting several hot reloaded libraries side by side.
+
+use crate::dynamic::{Hotload, Module, BUILD_POLL_INTERVAL};
+use crate::registry::Registry;
+use crate::Error;
+use fehui::View;
+use std::any::Any;
+use std::time::{Duration, Instant};
+
+/// Host of several independently reloaded plugin modules sharing one `FehUI`.
+///
+/// Modules register their widgets and screens by name in a shared `Registry`, which the host
+/// uses to assemble the UI. Reloading a module only replaces the plugin, the registered
+/// widgets and the states of this module, persistent states are restored afterwards.
+///
+/// States of views created via the registry belong to the module which registered the widget.
+/// States created by other widgets belong to the most recently loaded module.
+///
+/// Only available with the `dynamic` feature. Each module exports the unmangled `fehui_version`,
+/// `fehui_plugin` and `fehui_unload` symbols, statically linking several modules into one binary
+/// would define these multiple times. Static builds need a single app crate registering the
+/// widgets of all modules instead.
+pub struct ModuleHost {
+    modules: Vec<Hotload>,
+    registry: Registry,
+}
+
+impl ModuleHost {
+    pub fn new() -> Self {
+        ModuleHost {
+            modules: Vec::new(),
+            registry: Registry::new(),
+        }
+    }
+
+    /// Add a module, which will be loaded on the next `reload` or file change.
+    pub fn add(&mut self, name: &str, mut hotload: Hotload) {
+        hotload.module = Some(Module {
+            name: name.to_string(),
+            registry: self.registry.clone(),
+        });
+        self.modules.push(hotload);
+    }
+
+    /// Hotloader of the module.
+    pub fn module(&mut self, name: &str) -> Option<&mut Hotload> {
+        self.modules.iter_mut().find(|hotload| hotload.module_name() == Some(name))
+    }
+
+    pub fn registry(&self) -> Registry {
+        self.registry.clone()
+    }
+
+    /// Create a view of the widget registered by any module as `name`.
+    pub fn view(&self, name: &str) -> Option<View> {
+        self.registry.view(name)
+    }
+
+    /// Load all modules, continuing with the remaining ones if a module fails.
+    pub fn reload(&mut self, ui: &mut fehui::FehUI) -> Result<(), Error> {
+        let mut result = Ok(());
+        for hotload in &mut self.modules {
+            if let Err(err) = hotload.reload(ui) {
+                result = Err(err);
+            }
+        }
+        result
+    }
+
+    /// Reload changed modules, returns `true` if any module has been reloaded.
+    ///
+    /// The UI needs to be rebuilt afterwards to pick up the new widgets.
+    pub fn try_reload(&mut self, ui: &mut fehui::FehUI) -> Result<bool, Error> {
+        let mut reloaded = false;
+        let mut result = Ok(());
+        for hotload in &mut self.modules {
+            match hotload.try_reload(ui) {
+                Ok(module_reloaded) => reloaded |= module_reloaded,
+                Err(err) => result = Err(err),
+            }
+        }
+        result.map(|()| reloaded)
+    }
+
+    /// Block until any module has been reloaded or the timeout elapsed.
+    pub fn wait_for_reload(
+        &mut self,
+        ui: &mut fehui::FehUI,
+        timeout: Duration,
+    ) -> Result<bool, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.try_reload(ui)? {
+                return Ok(true);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            std::thread::sleep(BUILD_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// Run the frame callbacks of all modules.
+    pub fn frame(&mut self, ui: &mut fehui::FehUI) -> Result<(), Error> {
+        let mut result = Ok(());
+        for hotload in &mut self.modules {
+            if let Err(err) = hotload.frame(ui) {
+                result = Err(err);
+            }
+        }
+        result
+    }
+
+    /// Forward a host event to all modules.
+    pub fn event<E: Any>(&mut self, ui: &mut fehui::FehUI, event: &E) -> Result<(), Error> {
+        let mut result = Ok(());
+        for hotload in &mut self.modules {
+            if let Err(err) = hotload.event(ui, event) {
+                result = Err(err);
+            }
+        }
+        result
+    }
+}
+
+impl Default for ModuleHost {
+    fn default() -> Self {
+        ModuleHost::new()
+    }
+}
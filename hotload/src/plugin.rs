@@ -0,0 +1,165 @@
+use crate::api::catch_panic;
+use crate::registry::Registry;
+use std::any::{Any, TypeId};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::os::raw::c_void;
+
+/// Application logic living in a hot reloaded library.
+///
+/// The host owns the window and the `FehUI` instance and forwards the frame and event
+/// callbacks to the currently loaded plugin. A new plugin instance is created for each
+/// loaded library.
+pub trait Plugin<E = ()> {
+    /// Library has been loaded, e.g. build the initial widget tree.
+    fn on_load(&mut self, _ui: &mut fehui::FehUI) {}
+    /// Register widgets and screens by name, called before `on_load` if the library is
+    /// loaded as module of a `ModuleHost`.
+    fn on_register(&mut self, _registry: &Registry) {}
+    /// Library is going to be replaced by a newer version.
+    fn on_unload(&mut self, _ui: &mut fehui::FehUI) {}
+    fn on_frame(&mut self, _ui: &mut fehui::FehUI) {}
+    fn on_event(&mut self, _ui: &mut fehui::FehUI, _event: &E) {}
+}
+
+pub(crate) type Callback = extern "C" fn(*mut c_void, *mut fehui::FehUI, *mut String) -> u32;
+
+/// Plugin function table exported by libraries via `fehui_plugin!`.
+#[doc(hidden)]
+#[repr(C)]
+pub struct PluginVTable {
+    pub event_type: u64,
+    pub create: extern "C" fn() -> *mut c_void,
+    pub destroy: extern "C" fn(*mut c_void),
+    pub on_register: extern "C" fn(*mut c_void, *const Registry, *mut String) -> u32,
+    pub on_load: Callback,
+    pub on_unload: Callback,
+    pub on_frame: Callback,
+    pub on_event: extern "C" fn(*mut c_void, *mut fehui::FehUI, *const c_void, *mut String) -> u32,
+}
+
+/// Identifies event types across the library boundary.
+#[doc(hidden)]
+pub fn event_type<E: Any>() -> u64 {
+    let mut hasher = DefaultHasher::new();
+    TypeId::of::<E>().hash(&mut hasher);
+    std::any::type_name::<E>().hash(&mut hasher);
+    hasher.finish()
+}
+
+#[doc(hidden)]
+pub fn plugin_vtable<P, E>() -> PluginVTable
+where
+    P: Plugin<E> + Default,
+    E: Any,
+{
+    PluginVTable {
+        event_type: event_type::<E>(),
+        create: create::<P>,
+        destroy: destroy::<P>,
+        on_register: on_register::<P, E>,
+        on_load: on_load::<P, E>,
+        on_unload: on_unload::<P, E>,
+        on_frame: on_frame::<P, E>,
+        on_event: on_event::<P, E>,
+    }
+}
+
+// Returns a null pointer if the plugin creation panicked.
+extern "C" fn create<P: Default>() -> *mut c_void {
+    let mut plugin = std::ptr::null_mut();
+    let mut message = String::new();
+    catch_panic(
+        || plugin = Box::into_raw(Box::new(P::default())) as *mut _,
+        &mut message,
+    );
+    plugin
+}
+
+extern "C" fn destroy<P>(plugin: *mut c_void) {
+    let mut message = String::new();
+    catch_panic(
+        || unsafe { drop(Box::from_raw(plugin as *mut P)) },
+        &mut message,
+    );
+}
+
+extern "C" fn on_register<P: Plugin<E>, E>(
+    plugin: *mut c_void,
+    registry: *const Registry,
+    message: *mut String,
+) -> u32 {
+    let (plugin, registry) = unsafe { (&mut *(plugin as *mut P), &*registry) };
+    catch_panic(|| plugin.on_register(registry), unsafe { &mut *message })
+}
+
+extern "C" fn on_load<P: Plugin<E>, E>(
+    plugin: *mut c_void,
+    ui: *mut fehui::FehUI,
+    message: *mut String,
+) -> u32 {
+    let (plugin, ui) = unsafe { (&mut *(plugin as *mut P), &mut *ui) };
+    catch_panic(|| plugin.on_load(ui), unsafe { &mut *message })
+}
+
+extern "C" fn on_unload<P: Plugin<E>, E>(
+    plugin: *mut c_void,
+    ui: *mut fehui::FehUI,
+    message: *mut String,
+) -> u32 {
+    let (plugin, ui) = unsafe { (&mut *(plugin as *mut P), &mut *ui) };
+    catch_panic(|| plugin.on_unload(ui), unsafe { &mut *message })
+}
+
+extern "C" fn on_frame<P: Plugin<E>, E>(
+    plugin: *mut c_void,
+    ui: *mut fehui::FehUI,
+    message: *mut String,
+) -> u32 {
+    let (plugin, ui) = unsafe { (&mut *(plugin as *mut P), &mut *ui) };
+    catch_panic(|| plugin.on_frame(ui), unsafe { &mut *message })
+}
+
+extern "C" fn on_event<P: Plugin<E>, E>(
+    plugin: *mut c_void,
+    ui: *mut fehui::FehUI,
+    event: *const c_void,
+    message: *mut String,
+) -> u32 {
+    let (plugin, ui) = unsafe { (&mut *(plugin as *mut P), &mut *ui) };
+    let event = unsafe { &*(event as *const E) };
+    catch_panic(|| plugin.on_event(ui, event), unsafe { &mut *message })
+}
+
+/// Export a `Plugin` implementation from a hot reloaded library.
+///
+/// ```ignore
+/// fehui_plugin!(App, winit::Event);
+/// ```
+#[macro_export]
+macro_rules! fehui_plugin {
+    ($plugin:ty) => {
+        $crate::fehui_plugin!($plugin, (), || ());
+    };
+    ($plugin:ty, $event:ty) => {
+        $crate::fehui_plugin!($plugin, $event, || ());
+    };
+    ($plugin:ty, $event:ty, $unload:expr) => {
+        #[no_mangle]
+        extern "C" fn fehui_version() -> $crate::ApiVersion {
+            $crate::api_version()
+        }
+
+        #[no_mangle]
+        extern "C" fn fehui_plugin() -> $crate::PluginVTable {
+            $crate::plugin_vtable::<$plugin, $event>()
+        }
+
+        /// Called before the library gets unloaded, nothing of the library is used by the UI anymore.
+        #[no_mangle]
+        extern "C" fn fehui_unload() {
+            let mut message = String::new();
+            $crate::catch_panic(|| $unload(), &mut message);
+        }
+    };
+}
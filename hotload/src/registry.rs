@@ -0,0 +1,128 @@
+use fehui::{Build, Layout, Layouter, View, Widget};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+struct Factory {
+    module: String,
+    generation: u64, // code generation of the module's library
+    create: Rc<Fn() -> View>,
+}
+
+#[derive(Default)]
+struct RegistryInner {
+    factories: HashMap<String, Factory>,
+    module: String, // module currently registering
+    generation: u64,
+}
+
+// Attributes the states of a registered widget to the library of its module.
+#[derive(Debug)]
+struct ModuleView {
+    generation: u64,
+    child: View,
+}
+
+impl Widget for ModuleView {
+    fn build(&self, mut build: Build) {
+        build.set_generation(self.generation);
+        unsafe { build.add_view(&self.child) };
+    }
+
+    fn layout(&self, _: Layouter) -> Layout {
+        Layout::Pass
+    }
+}
+
+/// Widgets and screens registered by name from plugin modules.
+///
+/// Cloning returns a handle to the same registry. Factories of a module are removed before
+/// its library gets replaced, views need to be created again after each reload.
+#[derive(Clone, Default)]
+pub struct Registry(Rc<RefCell<RegistryInner>>);
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    /// Register a widget factory, replacing previous registrations of the same name.
+    pub fn register<W, F>(&self, name: &str, factory: F)
+    where
+        W: Widget,
+        F: Fn() -> W + 'static,
+    {
+        let mut inner = self.0.borrow_mut();
+        let module = inner.module.clone();
+        let generation = inner.generation;
+        inner.factories.insert(
+            name.to_string(),
+            Factory {
+                module,
+                generation,
+                create: Rc::new(move || -> View { factory().into() }),
+            },
+        );
+    }
+
+    /// Create a view of the widget registered as `name`.
+    ///
+    /// The widget is wrapped, so its states are saved and restored with the module's library.
+    pub fn view(&self, name: &str) -> Option<View> {
+        // Factories may create views of other registered widgets.
+        let (generation, create) = {
+            let inner = self.0.borrow();
+            let factory = inner.factories.get(name)?;
+            (factory.generation, factory.create.clone())
+        };
+        Some(
+            ModuleView {
+                generation,
+                child: create(),
+            }
+            .into(),
+        )
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.borrow().factories.contains_key(name)
+    }
+
+    /// Names of all registered widgets.
+    pub fn names(&self) -> Vec<String> {
+        self.0.borrow().factories.keys().cloned().collect()
+    }
+
+    /// Module which registered the widget.
+    pub fn module_of(&self, name: &str) -> Option<String> {
+        self.0
+            .borrow()
+            .factories
+            .get(name)
+            .map(|factory| factory.module.clone())
+    }
+
+    #[cfg(feature = "dynamic")]
+    pub(crate) fn set_module(&self, module: &str, generation: u64) {
+        let mut inner = self.0.borrow_mut();
+        inner.module = module.to_string();
+        inner.generation = generation;
+    }
+
+    /// Remove all factories registered by the module.
+    #[cfg(feature = "dynamic")]
+    pub(crate) fn remove_module(&self, module: &str) {
+        self.0
+            .borrow_mut()
+            .factories
+            .retain(|_, factory| factory.module != module);
+    }
+}
+
+impl std::fmt::Debug for Registry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_set()
+            .entries(self.0.borrow().factories.keys())
+            .finish()
+    }
+}
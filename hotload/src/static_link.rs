@@ -0,0 +1,195 @@
+//! Statically linked app, used for release builds without hot reloading.
+//!
+//! The app crate exporting the plugin via `fehui_hook!` or `fehui_plugin!` needs to be linked
+//! into the host binary, e.g. by `extern crate app;`. Paths passed to `Hotload` are ignored.
+//!
+//! Enabled by the `static` feature, the default `dynamic` feature needs to be disabled so
+//! static builds don't pull in the dependencies used for loading and watching libraries.
+//!
+//! `ModuleHost` isn't supported, only a single app crate can export the plugin symbols.
+
+use crate::api::CALL_OK;
+use crate::assets::AssetDir;
+use crate::plugin::{event_type, Callback, PluginVTable};
+use crate::{Error, Listeners, ReloadEvent, Target};
+use std::any::Any;
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+extern "C" {
+    fn fehui_plugin() -> PluginVTable;
+    fn fehui_unload();
+}
+
+struct Entry {
+    vtable: PluginVTable,
+    plugin: *mut c_void,
+}
+
+impl Entry {
+    fn new() -> Result<Self, Error> {
+        let vtable = unsafe { fehui_plugin() };
+        let plugin = (vtable.create)();
+        if plugin.is_null() {
+            return Err(Error::Panicked("plugin creation panicked".into()));
+        }
+
+        Ok(Entry { vtable, plugin })
+    }
+
+    fn call(&mut self, ui: &mut fehui::FehUI, callback: Callback) -> Result<(), Error> {
+        let mut message = String::new();
+        match callback(self.plugin, ui as *mut _, &mut message as *mut _) {
+            CALL_OK => Ok(()),
+            _ => Err(Error::Panicked(message)),
+        }
+    }
+
+    fn event<E: Any>(&mut self, ui: &mut fehui::FehUI, event: &E) -> Result<(), Error> {
+        if self.vtable.event_type != event_type::<E>() {
+            return Ok(());
+        }
+
+        let mut message = String::new();
+        let event = event as *const E as *const c_void;
+        match (self.vtable.on_event)(self.plugin, ui as *mut _, event, &mut message as *mut _) {
+            CALL_OK => Ok(()),
+            _ => Err(Error::Panicked(message)),
+        }
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        (self.vtable.destroy)(self.plugin);
+    }
+}
+
+/// Drop-in replacement of the hot reloading `Hotload`, calling directly into the linked app.
+///
+/// The app is loaded by the first `reload`, afterwards reloading only recreates the plugin.
+/// The unload function of the app is called once when dropping the `Hotload`.
+pub struct Hotload {
+    path: PathBuf,
+    entry: Option<Entry>,
+    loaded: bool, // plugin has been created at least once
+    assets: Option<AssetDir>,
+    listeners: Listeners,
+}
+
+impl Hotload {
+    pub fn new<P: AsRef<Path>>(path: P, _lib_name: &str) -> Result<Self, Error> {
+        Ok(Hotload {
+            path: path.as_ref().to_path_buf(),
+            entry: None,
+            loaded: false,
+            assets: None,
+            listeners: Listeners::new(),
+        })
+    }
+
+    pub fn cargo<P: AsRef<Path>>(manifest_dir: P, _target: Target) -> Result<Self, Error> {
+        Hotload::new(manifest_dir, "")
+    }
+
+    /// Load the assets of the directory once on the next reload, changes aren't watched.
+    pub fn watch_assets<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), Error> {
+        self.assets = Some(AssetDir::new(dir)?);
+        Ok(())
+    }
+
+    pub fn build(&mut self) {}
+
+    pub fn is_building(&self) -> bool {
+        false
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.entry.is_some()
+    }
+
+    /// Register a callback, which will be called after each reload attempt.
+    pub fn on_reload<F: FnMut(&ReloadEvent) + 'static>(&mut self, callback: F) {
+        self.listeners.add(callback);
+    }
+
+    /// Channel receiving an event after each reload attempt.
+    pub fn subscribe(&mut self) -> Receiver<ReloadEvent> {
+        self.listeners.subscribe()
+    }
+
+    /// Load the app if not done yet, there are no changes to pick up otherwise.
+    pub fn try_reload(&mut self, ui: &mut fehui::FehUI) -> Result<bool, Error> {
+        if self.entry.is_some() {
+            return Ok(false);
+        }
+        self.reload(ui).map(|_| true)
+    }
+
+    pub fn wait_for_reload(&mut self, ui: &mut fehui::FehUI, timeout: Duration) -> Result<bool, Error> {
+        if self.entry.is_none() {
+            return self.try_reload(ui);
+        }
+        std::thread::sleep(timeout);
+        Ok(false)
+    }
+
+    /// Recreate the plugin of the app.
+    pub fn reload(&mut self, ui: &mut fehui::FehUI) -> Result<(), Error> {
+        let start = Instant::now();
+        let result = self.load(ui);
+        self.listeners.dispatch(&self.path, start.elapsed(), &result);
+        result
+    }
+
+    fn load(&mut self, ui: &mut fehui::FehUI) -> Result<(), Error> {
+        if let Some(mut prev) = self.entry.take() {
+            let on_unload = prev.vtable.on_unload;
+            let _ = prev.call(ui, on_unload);
+        }
+
+        // Asset errors are returned after loading, the app is loaded regardless.
+        let assets_updated = match self.assets {
+            Some(ref mut assets) => assets.update(ui).map(|_| ()),
+            None => Ok(()),
+        };
+
+        let mut entry = Entry::new()?;
+        self.loaded = true;
+        let on_load = entry.vtable.on_load;
+        entry.call(ui, on_load)?;
+        self.entry = Some(entry);
+        assets_updated
+    }
+
+    pub fn frame(&mut self, ui: &mut fehui::FehUI) -> Result<(), Error> {
+        match self.entry {
+            Some(ref mut entry) => {
+                let on_frame = entry.vtable.on_frame;
+                entry.call(ui, on_frame)
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn event<E: Any>(&mut self, ui: &mut fehui::FehUI, event: &E) -> Result<(), Error> {
+        match self.entry {
+            Some(ref mut entry) => entry.event(ui, event),
+            None => Ok(()),
+        }
+    }
+
+    /// Nothing to unload, the code stays linked into the binary.
+    pub fn unload_unused(&mut self, _ui: &fehui::FehUI) {}
+}
+
+impl Drop for Hotload {
+    fn drop(&mut self) {
+        self.entry = None;
+        if self.loaded {
+            unsafe { fehui_unload() }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Statically linked app, run with `cargo test --no-default-features --features static`.
+#![cfg(feature = "static")]
+
+use fehui_hotload::{fehui_plugin, Hotload, Plugin};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static LOADS: AtomicUsize = AtomicUsize::new(0);
+static UNLOADS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Default)]
+struct App;
+
+impl Plugin for App {
+    fn on_load(&mut self, _: &mut fehui::FehUI) {
+        LOADS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fehui_plugin!(App, (), || {
+    UNLOADS.fetch_add(1, Ordering::SeqCst);
+});
+
+#[test]
+fn unload_on_drop() {
+    let mut ui = fehui::FehUI::new();
+    let mut hotload = Hotload::new(".", "app").unwrap();
+    assert!(hotload.try_reload(&mut ui).unwrap());
+    assert!(!hotload.try_reload(&mut ui).unwrap());
+    hotload.reload(&mut ui).unwrap();
+    assert_eq!(LOADS.load(Ordering::SeqCst), 2);
+
+    // The linked code stays in use, only recreating the plugin.
+    assert_eq!(UNLOADS.load(Ordering::SeqCst), 0);
+    drop(hotload);
+    assert_eq!(UNLOADS.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,105 @@
+//! Non-code resources like stylesheets, images, fonts or string tables.
+//!
+//! Assets are stored as raw bytes by name and decoded on first access during a build.
+//! Replacing an asset via `FehUI::set_asset` disables memoization for the next build,
+//! so widgets reading it will pick up the new version.
+
+use crate::{Build, FehUI};
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Types which can be decoded from asset data.
+pub trait FromAsset: Sized + 'static {
+    fn from_asset(data: &[u8]) -> Option<Self>;
+}
+
+impl FromAsset for Vec<u8> {
+    fn from_asset(data: &[u8]) -> Option<Self> {
+        Some(data.to_vec())
+    }
+}
+
+impl FromAsset for String {
+    fn from_asset(data: &[u8]) -> Option<Self> {
+        String::from_utf8(data.to_vec()).ok()
+    }
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+struct Asset {
+    #[derivative(Debug = "ignore")]
+    data: Rc<[u8]>,
+    #[derivative(Debug = "ignore")]
+    decoded: RefCell<HashMap<TypeId, Rc<Any>>>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Assets {
+    assets: HashMap<String, Asset>,
+    pub changed: bool, // assets changed since the last build
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        Assets::default()
+    }
+
+    fn get<T: FromAsset>(&self, name: &str) -> Option<Rc<T>> {
+        let asset = self.assets.get(name)?;
+        let ty = TypeId::of::<T>();
+        if let Some(decoded) = asset.decoded.borrow().get(&ty) {
+            return decoded.clone().downcast::<T>().ok();
+        }
+
+        let decoded = Rc::new(T::from_asset(&asset.data)?);
+        asset
+            .decoded
+            .borrow_mut()
+            .insert(ty, decoded.clone() as Rc<Any>);
+        Some(decoded)
+    }
+
+    /// Drop all decoded values, their types might belong to unloaded code.
+    pub fn clear_decoded(&mut self) {
+        for asset in self.assets.values_mut() {
+            asset.decoded.get_mut().clear();
+        }
+    }
+}
+
+impl FehUI {
+    /// Add or replace an asset, widgets will see the new data on the next build.
+    pub fn set_asset<S: Into<String>, D: Into<Rc<[u8]>>>(&mut self, name: S, data: D) {
+        self.assets.assets.insert(
+            name.into(),
+            Asset {
+                data: data.into(),
+                decoded: RefCell::new(HashMap::new()),
+            },
+        );
+        self.assets.changed = true;
+    }
+
+    pub fn remove_asset(&mut self, name: &str) {
+        if self.assets.assets.remove(name).is_some() {
+            self.assets.changed = true;
+        }
+    }
+
+    /// Raw data of an asset.
+    pub fn asset_data(&self, name: &str) -> Option<Rc<[u8]>> {
+        self.assets.assets.get(name).map(|asset| asset.data.clone())
+    }
+}
+
+impl Build<'_> {
+    /// Fetch an asset decoded as `T`, decoded values are cached until the asset changes.
+    ///
+    /// Returns `None` if the asset doesn't exist or can't be decoded.
+    pub fn asset<T: FromAsset>(&self, name: &str) -> Option<Rc<T>> {
+        self.assets.get(name)
+    }
+}
@@ -0,0 +1,75 @@
+//! Input events dispatched to widgets.
+
+use crate::{FehUI, Id, StateRef, StateSlot};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// Mouse click at a position in UI coordinates.
+    Click { x: f32, y: f32 },
+    KeyPress(InputKey),
+    /// Text input, e.g. typed characters or IME commits.
+    Text(String),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InputKey {
+    Char(char),
+    Enter,
+    Escape,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+}
+
+/// Context of `Widget::event`, giving access to the states of the handling widget.
+pub struct EventCtxt<'a> {
+    ui: &'a FehUI,
+    id: Id,
+}
+
+impl EventCtxt<'_> {
+    /// Widget handling the event.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// State of the widget, `None` if it doesn't exist (e.g. for widgets without key).
+    ///
+    /// Mutating the state marks it as changed, see `Widget::memo`.
+    pub fn state(&self, slot: StateSlot) -> Option<StateRef> {
+        let key = self.ui.widget_key(self.id)?;
+        self.ui.state(self.ui.states.intern_key(key), slot)
+    }
+}
+
+impl FehUI {
+    /// Dispatch an event to the innermost widget at the position.
+    ///
+    /// The event bubbles up to the root until a widget handles it, returns `true` if handled.
+    /// Requires a layout of the current widget tree.
+    pub fn dispatch(&self, x: f32, y: f32, event: &Event) -> bool {
+        match self.hit_test(x, y) {
+            Some(id) => self.dispatch_to(id, event),
+            None => false,
+        }
+    }
+
+    /// Dispatch an event to the widget, bubbling up to the root until a widget handles it.
+    pub fn dispatch_to(&self, id: Id, event: &Event) -> bool {
+        let mut target = Some(id);
+        while let Some(id) = target {
+            let mut ctxt = EventCtxt { ui: self, id };
+            if self.widget(id).event(&mut ctxt, event) {
+                return true;
+            }
+            target = self.parent(id);
+        }
+        false
+    }
+}
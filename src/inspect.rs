@@ -0,0 +1,113 @@
+//! Read access to the built widget tree, e.g. for tests and debugging tools.
+
+use crate::layout::{LayoutNode, Rect};
+use crate::{FehUI, Id, Key, StateRef, StateSlot, Widget};
+
+impl FehUI {
+    pub fn widget(&self, id: Id) -> &Widget {
+        &*self.widgets[id.widget]
+    }
+
+    /// Global or local key of the widget, only keyed widgets can have states.
+    pub fn widget_key(&self, id: Id) -> Option<Key> {
+        self.keys[id.widget]
+    }
+
+    pub fn parent(&self, id: Id) -> Option<Id> {
+        let node = self.graph[id.node].parent()?;
+        Some(Id {
+            widget: self.graph[node].data,
+            node,
+        })
+    }
+
+    pub fn children(&self, id: Id) -> Vec<Id> {
+        id.node
+            .children(&self.graph)
+            .map(|node| Id {
+                widget: self.graph[node].data,
+                node,
+            })
+            .collect()
+    }
+
+    /// All widgets of the current tree in depth-first order.
+    pub fn descendants(&self) -> Vec<Id> {
+        match self.root {
+            Some(root) => root
+                .node
+                .descendants(&self.graph)
+                .map(|node| Id {
+                    widget: self.graph[node].data,
+                    node,
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Bounds of the widget computed by the last layout.
+    ///
+    /// Widgets without own layout (`Layout::Pass`) share the bounds of their parent.
+    pub fn rect(&self, id: Id) -> Rect {
+        let parent = || match self.parent(id) {
+            Some(parent) => self.rect(parent),
+            None => Rect {
+                x: 0.0,
+                y: 0.0,
+                width: self.viewport.0,
+                height: self.viewport.1,
+            },
+        };
+
+        match self.layouts[id.widget] {
+            LayoutNode::FlexRoot(node) => {
+                let (width, height) = self.engine.size(node);
+                Rect {
+                    x: 0.0,
+                    y: 0.0,
+                    width,
+                    height,
+                }
+            }
+            LayoutNode::Flex { node, .. } => {
+                let parent = parent();
+                let (x, y) = self.engine.position(node);
+                let (width, height) = self.engine.size(node);
+                Rect {
+                    x: parent.x + x,
+                    y: parent.y + y,
+                    width,
+                    height,
+                }
+            }
+            LayoutNode::None => parent(),
+        }
+    }
+
+    /// Innermost widget at the position, later siblings are considered to be on top.
+    pub fn hit_test(&self, x: f32, y: f32) -> Option<Id> {
+        let mut id = self.root?;
+        if !self.rect(id).contains(x, y) {
+            return None;
+        }
+
+        'descend: loop {
+            for child in self.children(id).into_iter().rev() {
+                if self.rect(child).contains(x, y) {
+                    id = child;
+                    continue 'descend;
+                }
+            }
+            return Some(id);
+        }
+    }
+
+    /// State of a keyed widget, see `Build::create_state`.
+    pub fn state(&self, key: Key, slot: StateSlot) -> Option<StateRef> {
+        self.states
+            .states
+            .get(&(key, slot))
+            .map(|state| state.data.clone())
+    }
+}
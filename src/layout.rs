@@ -1,5 +1,9 @@
 use crate::storage::Storage;
 use crate::WidgetId;
+use std::collections::HashMap;
+use stretch::geometry::Size;
+use stretch::number::ToNumber;
+use stretch::Stretch;
 
 #[derive(Debug, Clone)]
 pub enum Layout {
@@ -7,15 +11,120 @@ pub enum Layout {
     Pass,
 }
 
+impl From<stretch::style::Style> for Layout {
+    fn from(style: stretch::style::Style) -> Self {
+        Layout::Flex(style)
+    }
+}
+
+/// Opaque handle to a node owned by a `LayoutEngine`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EngineNode(pub usize);
+
+/// Backend computing the actual positions and sizes of the layout tree.
+///
+/// Widgets describe their layout with flexbox styles (`Layout::Flex`), the engine
+/// is free to interpret these as it likes.
+pub trait LayoutEngine {
+    fn new_node(&mut self, style: &stretch::style::Style) -> EngineNode;
+    fn add_child(&mut self, parent: EngineNode, child: EngineNode);
+    fn remove(&mut self, node: EngineNode);
+    fn compute_layout(&mut self, root: EngineNode, width: f32, height: f32);
+    /// Computed size of a node, only valid after `compute_layout` of its root.
+    fn size(&self, node: EngineNode) -> (f32, f32);
+    /// Computed position of a node relative to its parent node.
+    fn position(&self, node: EngineNode) -> (f32, f32);
+}
+
+/// Computed bounds of a widget in UI coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+}
+
+/// Default layout engine based on `stretch`.
+pub struct StretchEngine {
+    stretch: Stretch,
+    nodes: HashMap<EngineNode, stretch::node::Node>,
+    next: usize,
+}
+
+impl StretchEngine {
+    pub fn new() -> Self {
+        StretchEngine {
+            stretch: Stretch::new(),
+            nodes: HashMap::new(),
+            next: 0,
+        }
+    }
+}
+
+impl LayoutEngine for StretchEngine {
+    fn new_node(&mut self, style: &stretch::style::Style) -> EngineNode {
+        let node = self.stretch.new_node(style.clone(), vec![]).unwrap();
+        let id = EngineNode(self.next);
+        self.next += 1;
+        self.nodes.insert(id, node);
+        id
+    }
+
+    fn add_child(&mut self, parent: EngineNode, child: EngineNode) {
+        self.stretch
+            .add_child(self.nodes[&parent], self.nodes[&child])
+            .unwrap();
+    }
+
+    fn remove(&mut self, node: EngineNode) {
+        if let Some(node) = self.nodes.remove(&node) {
+            self.stretch.remove(node);
+        }
+    }
+
+    fn compute_layout(&mut self, root: EngineNode, width: f32, height: f32) {
+        self.stretch
+            .compute_layout(
+                self.nodes[&root],
+                Size {
+                    width: width.to_number(),
+                    height: height.to_number(),
+                },
+            )
+            .unwrap();
+    }
+
+    fn size(&self, node: EngineNode) -> (f32, f32) {
+        let layout = self.stretch.layout(self.nodes[&node]).unwrap();
+        (layout.size.width, layout.size.height)
+    }
+
+    fn position(&self, node: EngineNode) -> (f32, f32) {
+        let layout = self.stretch.layout(self.nodes[&node]).unwrap();
+        (layout.location.x, layout.location.y)
+    }
+}
+
 pub enum LayoutNode {
-    FlexRoot(stretch::node::Node),
-    Flex { node: stretch::node::Node, root: stretch::node::Node },
+    FlexRoot(EngineNode),
+    Flex { node: EngineNode, root: EngineNode },
     None,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum ParentLayout {
-    Node { node: stretch::node::Node, root: stretch::node::Node },
+    Node { node: EngineNode, root: EngineNode },
 }
 
 pub type Layouts = Storage<LayoutNode>;
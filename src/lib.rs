@@ -1,24 +1,35 @@
 #[macro_use]
 extern crate derivative;
 
+mod assets;
+mod event;
+mod inspect;
 mod layout;
+#[cfg(feature = "persist")]
+mod persist;
 mod storage;
 mod view;
 pub mod widgets;
 
-pub use layout::Layout;
-pub use view::View;
+pub use assets::FromAsset;
+pub use event::{Event, EventCtxt, InputKey};
+pub use layout::{EngineNode, Layout, LayoutEngine, Rect, StretchEngine};
+#[cfg(feature = "persist")]
+pub use persist::SavedStates;
+pub use view::{Children, IntoChildren, View};
 
+use assets::Assets;
 use layout::{LayoutNode, Layouts, ParentLayout};
 use std::any::{Any, TypeId};
-use std::cell::RefCell;
+use std::cell::{Cell, Ref, RefCell, RefMut};
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::time::Duration;
 use storage::{Keys, Widgets};
-use stretch::geometry::Size;
-use stretch::number::ToNumber;
-use stretch::Stretch;
+
+/// Version of the `fehui` crate, e.g. for checking compatibility of hotloaded libraries.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub type NodeId = indextree::NodeId; // temp
 type Graph = indextree::Arena<WidgetId>;
@@ -31,13 +42,25 @@ pub struct FehUI {
     widget_ids: WidgetIdGen,
     pub root: Option<Id>, // temp pub
     keys: Keys,
+    prev: PrevTree,
     states: StateCache,
     layouts: Layouts,
-    stretch: Stretch,
+    assets: Assets,
+    engine: Box<LayoutEngine>,
+    layout_dirty: bool, // widget tree changed since the last layout tree generation
+    viewport: (f32, f32), // size of the last layout
+    time: Duration,
+    generation: u64,    // code generation used for new widgets and states
+    tree_generation: Option<u64>, // code generation of the current widget tree
 }
 
 impl FehUI {
     pub fn new() -> Self {
+        Self::with_engine(StretchEngine::new())
+    }
+
+    /// Create a new UI instance using a custom layout engine.
+    pub fn with_engine<E: LayoutEngine + 'static>(engine: E) -> Self {
         FehUI {
             graph: Graph::new(),
             graph_map: GraphMap::new(),
@@ -45,15 +68,80 @@ impl FehUI {
             root: None,
             widget_ids: WidgetIdGen::new(),
             keys: Keys::new(),
+            prev: PrevTree::new(),
             states: StateCache::new(),
             layouts: Layouts::new(),
-            stretch: Stretch::new(),
+            assets: Assets::new(),
+            engine: Box::new(engine),
+            layout_dirty: false,
+            viewport: (0.0, 0.0),
+            time: Duration::default(),
+            generation: 0,
+            tree_generation: None,
         }
     }
 
+    /// Set the code generation for all widgets and states created afterwards.
+    ///
+    /// Used for tracking which code is still referenced by the UI, e.g. when
+    /// hot reloading libraries. Memoized subtrees won't be reused across generations.
+    pub fn set_generation(&mut self, generation: u64) {
+        if generation != self.generation {
+            self.assets.clear_decoded();
+        }
+        self.generation = generation;
+    }
+
+    /// Code generations still referenced by widgets, states or effects.
+    pub fn generations_in_use(&self) -> HashSet<u64> {
+        let mut generations = HashSet::new();
+        generations.extend(self.tree_generation);
+        generations.extend(self.prev.generation);
+        generations.extend(self.states.states.values().map(|state| state.generation));
+        generations.extend(self.states.effects.values().map(|effect| effect.generation));
+        generations
+    }
+
     pub fn build(&mut self, widget: impl Widget) {
+        self.build_root(Rc::new(widget));
+    }
+
+    /// Build the current root widget again, e.g. after assets changed.
+    ///
+    /// Returns `false` if there is no root widget or it has been created by an older
+    /// code generation.
+    pub fn rebuild(&mut self) -> bool {
+        match self.root {
+            Some(root) if self.tree_generation == Some(self.generation) => {
+                let widget = self.widgets[root.widget].clone();
+                self.build_root(widget);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn build_root(&mut self, widget: Rc<Widget>) {
         self.widget_ids = WidgetIdGen::new();
-        self.graph = Graph::new();
+        // The previous build pass might have panicked before running its effects.
+        self.states.discard_pending_effects();
+
+        // Keep the previous widget tree around for reusing memoized subtrees.
+        // Memoized widgets might depend on changed assets, which requires a full build.
+        let assets_changed = std::mem::replace(&mut self.assets.changed, false);
+        if self.tree_generation == Some(self.generation) && !assets_changed {
+            self.prev.root = self.root;
+            self.prev.generation = self.tree_generation;
+            self.prev.graph = std::mem::replace(&mut self.graph, Graph::new());
+            std::mem::swap(&mut self.prev.graph_map, &mut self.graph_map);
+            std::mem::swap(&mut self.prev.widgets, &mut self.widgets);
+            std::mem::swap(&mut self.prev.keys, &mut self.keys);
+            self.prev.update_key_map();
+        } else {
+            self.prev = PrevTree::new();
+            self.graph = Graph::new();
+        }
+        self.tree_generation = Some(self.generation);
 
         let root = {
             let widget = self.widget_ids.generate();
@@ -61,6 +149,7 @@ impl FehUI {
             Id { widget, node }
         };
 
+        self.free_layout();
         self.widgets.clear();
         self.graph_map.clear();
         self.keys.clear();
@@ -69,9 +158,10 @@ impl FehUI {
         self.root = Some(root);
 
         self.keys.push(Some(GlobalKeygen::ROOT_KEY));
-        self.widgets.push(Box::new(widget));
+        self.widgets.push(widget);
         self.graph_map.insert(root.widget, root.node);
         self.layouts.push(LayoutNode::None);
+        self.layout_dirty = true;
 
         let build = Build {
             id: root,
@@ -82,17 +172,66 @@ impl FehUI {
             keys: &mut self.keys,
             layouts: &mut self.layouts,
             states: &mut self.states,
+            assets: &self.assets,
+            time: self.time,
+            prev: &self.prev,
+            prev_id: self.prev.root,
+            state_index: 0,
+            generation: self.generation,
         };
 
         // println!("build :: build {:?}", root);
+        self.states.collect_changed();
         self.widgets[root.widget].build(build);
+        // Changes made while building are already part of the new tree.
+        self.states.reset_changed();
+        // Saved states not picked up by this pass belong to removed widgets.
+        #[cfg(feature = "persist")]
+        self.states.saved.clear();
+        self.states.flush_effects();
     }
 
+    /// Layout the current widget tree.
+    ///
+    /// The layout tree is only regenerated if the widget tree has been rebuilt in the meantime,
+    /// otherwise only the sizes are recomputed (e.g on window resize).
     pub fn layout(&mut self, width: f32, height: f32) {
-        self.generate_layout();
+        self.viewport = (width, height);
+        if self.layout_dirty {
+            self.generate_layout();
+            self.layout_dirty = false;
+        }
         self.compute_layout(width, height);
     }
 
+    /// Set the time visible to widgets during builds, e.g. for animations.
+    ///
+    /// Only advanced by the host, which allows driving the UI with a fake clock.
+    pub fn set_time(&mut self, time: Duration) {
+        self.time = time;
+    }
+
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Take the state events (resets and migrations) emitted since the last call.
+    pub fn take_state_events(&mut self) -> Vec<StateEvent> {
+        std::mem::replace(&mut self.states.events, Vec::new())
+    }
+
+    // Release all layout engine nodes of the current widget tree.
+    fn free_layout(&mut self) {
+        for i in 0..self.layouts.len() {
+            match *self.layouts.get(i) {
+                LayoutNode::FlexRoot(node) | LayoutNode::Flex { node, .. } => {
+                    self.engine.remove(node)
+                }
+                LayoutNode::None => (),
+            }
+        }
+    }
+
     fn generate_layout(&mut self) {
         if let Some(root) = self.root {
             Layouter {
@@ -100,7 +239,7 @@ impl FehUI {
                 widgets: &self.widgets,
                 graph: &self.graph,
                 layouts: &mut self.layouts,
-                stretch: &mut self.stretch,
+                engine: &mut *self.engine,
                 parent: None,
             }
             .generate_tree();
@@ -114,7 +253,7 @@ impl FehUI {
                 widgets: &self.widgets,
                 graph: &self.graph,
                 layouts: &mut self.layouts,
-                stretch: &mut self.stretch,
+                engine: &mut *self.engine,
                 parent: None,
             }
             .compute_layout(width, height);
@@ -122,9 +261,58 @@ impl FehUI {
     }
 }
 
-pub trait Widget: 'static + std::fmt::Debug {
+impl Drop for FehUI {
+    fn drop(&mut self) {
+        // All widgets get unmounted with the UI.
+        for (_, effect) in self.states.effects.drain() {
+            if let Some(cleanup) = effect.cleanup {
+                cleanup();
+            }
+        }
+    }
+}
+
+pub trait Widget: 'static + std::fmt::Debug + AsAny {
     fn build(&self, ctxt: Build);
     fn layout(&self, ctxt: Layouter) -> Layout;
+
+    /// Compare the widget against the widget of the previous build pass at the same
+    /// position or with the same key.
+    ///
+    /// If both are equal the previous subtree will be reused and `build` won't be called,
+    /// unless a state of the subtree has been mutably borrowed since the last build pass.
+    /// See `memo_eq` for a `PartialEq` based implementation.
+    fn memo(&self, _prev: &Widget) -> bool {
+        false
+    }
+
+    /// Handle an input event, returns `true` if handled. Unhandled events bubble up to the parent.
+    fn event(&self, _ctxt: &mut EventCtxt, _event: &Event) -> bool {
+        false
+    }
+}
+
+/// Upcast to `Any`, implemented for all widgets.
+pub trait AsAny {
+    fn as_any(&self) -> &Any;
+    fn type_name(&self) -> &'static str;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &Any {
+        self
+    }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+}
+
+/// `PartialEq` based memoization for `Widget::memo`.
+pub fn memo_eq<W: Widget + PartialEq>(widget: &W, prev: &Widget) -> bool {
+    prev.as_any()
+        .downcast_ref::<W>()
+        .map_or(false, |prev| widget == prev)
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -156,7 +344,7 @@ pub struct Layouter<'a> {
     widgets: &'a Widgets,
     graph: &'a Graph,
     layouts: &'a mut Layouts,
-    stretch: &'a mut Stretch,
+    engine: &'a mut LayoutEngine,
     parent: Option<layout::ParentLayout>,
 }
 
@@ -167,19 +355,16 @@ impl Layouter<'_> {
             widgets: &self.widgets,
             graph: &self.graph,
             layouts: &mut self.layouts,
-            stretch: &mut self.stretch,
+            engine: &mut *self.engine,
             parent: self.parent,
         });
 
-        println!("layout: {:#?}", self.id.widget);
-
         let (parent, layout_node) = match layout {
             Layout::Flex(style) => {
-                let node = self.stretch.new_node(style, vec![]).unwrap();
+                let node = self.engine.new_node(&style);
                 match self.parent {
                     Some(ParentLayout::Node { node: parent, root }) => {
-                        println!("  relation: {:#?}", (parent, node));
-                        self.stretch.add_child(parent, node).unwrap();
+                        self.engine.add_child(parent, node);
                         (
                             Some(ParentLayout::Node { node, root }),
                             LayoutNode::Flex { node, root },
@@ -198,7 +383,6 @@ impl Layouter<'_> {
         // Layout the children
         for child in self.id.node.children(&self.graph) {
             let child_id = self.graph[child].data;
-            println!("layout - child: {:#?}", (self.id.widget, child_id));
             Layouter {
                 id: Id {
                     node: child,
@@ -207,7 +391,7 @@ impl Layouter<'_> {
                 widgets: &self.widgets,
                 graph: &self.graph,
                 layouts: &mut self.layouts,
-                stretch: &mut self.stretch,
+                engine: &mut *self.engine,
                 parent,
             }
             .generate_tree();
@@ -219,28 +403,11 @@ impl Layouter<'_> {
 
         let (width, height) = match layout {
             LayoutNode::FlexRoot(node) => {
-                self.stretch
-                    .compute_layout(
-                        *node,
-                        Size {
-                            width: width.to_number(),
-                            height: height.to_number(),
-                        },
-                    )
-                    .unwrap();
-                let layout = self.stretch.layout(*node).unwrap();
-                println!("root: {:?}", layout);
-                (layout.size.width, layout.size.height)
-            }
-            LayoutNode::Flex { node, .. } => {
-                let layout = self.stretch.layout(*node).unwrap();
-                println!("node: {:?} ({:?})", layout, node);
-                (layout.size.width, layout.size.height)
-            }
-            LayoutNode::None => {
-                println!("pass: {:?}", (width, height));
-                (width, height)
+                self.engine.compute_layout(*node, width, height);
+                self.engine.size(*node)
             }
+            LayoutNode::Flex { node, .. } => self.engine.size(*node),
+            LayoutNode::None => (width, height),
         };
 
         // Layout the children
@@ -254,7 +421,7 @@ impl Layouter<'_> {
                 widgets: &self.widgets,
                 graph: &self.graph,
                 layouts: &mut self.layouts,
-                stretch: &mut self.stretch,
+                engine: &mut *self.engine,
                 parent: None,
             }
             .compute_layout(width, height);
@@ -271,9 +438,55 @@ pub struct Build<'a> {
     keys: &'a mut Keys,
     layouts: &'a mut Layouts,
     states: &'a mut StateCache,
+    assets: &'a Assets,
+    time: Duration,
+    prev: &'a PrevTree,
+    prev_id: Option<Id>, // matching widget of the previous build pass
+    state_index: usize,  // number of states created by the current widget
+    generation: u64,     // code generation of new states and effects
+}
+
+/// Shared handle to a widget state.
+///
+/// Mutable borrows mark the state as changed, memoized widgets holding changed states
+/// in their subtree will be rebuilt on the next build pass.
+#[derive(Clone, Debug)]
+pub struct StateRef(Rc<StateCell>);
+
+#[derive(Debug)]
+struct StateCell {
+    data: RefCell<Box<Any>>,
+    changed: Cell<bool>,
 }
 
-type StateInternal = Rc<RefCell<Box<Any>>>; // TODO: high very meh
+impl StateRef {
+    fn new(data: Box<Any>) -> Self {
+        StateRef(Rc::new(StateCell {
+            data: RefCell::new(data),
+            changed: Cell::new(false),
+        }))
+    }
+
+    pub fn borrow(&self) -> Ref<Box<Any>> {
+        self.0.data.borrow()
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<Box<Any>> {
+        self.0.changed.set(true);
+        self.0.data.borrow_mut()
+    }
+
+    // Take the state, only possible if there are no other handles.
+    fn try_unwrap(self) -> Option<Box<Any>> {
+        Rc::try_unwrap(self.0)
+            .ok()
+            .map(|cell| cell.data.into_inner())
+    }
+
+    fn take_changed(&self) -> bool {
+        self.0.changed.replace(false)
+    }
+}
 
 impl<'a> Build<'a> {
     fn clone(&mut self) -> Build {
@@ -286,6 +499,12 @@ impl<'a> Build<'a> {
             keys: self.keys,
             layouts: self.layouts,
             states: self.states,
+            assets: self.assets,
+            time: self.time,
+            prev: self.prev,
+            prev_id: self.prev_id,
+            state_index: self.state_index,
+            generation: self.generation,
         }
     }
 
@@ -302,100 +521,293 @@ impl<'a> Build<'a> {
     }
 
     fn append_child(&mut self, parent: NodeId, child: NodeId) {
-        println!("build :: append {:?} {:?}", parent, child);
         parent.append(child, self.graph).unwrap();
     }
 
+    fn intern(&mut self, widget: WidgetId) {
+        let prev = *self.states.prev_interns.get(&widget).unwrap_or(&widget);
+        self.states.next_interns.insert(widget, prev);
+    }
+
+    // Find the widget of the previous build pass corresponding to the next child.
+    fn prev_child(&self, global_key: Option<Key>) -> Option<Id> {
+        match global_key {
+            Some(key @ Key::Global(_)) => self.prev.key_map.get(&key).map(|&widget| Id {
+                widget,
+                node: self.prev.graph_map[&widget],
+            }),
+            _ => {
+                let prev_parent = self.prev_id?;
+                let index = self.id.node.children(self.graph).count();
+                let node = prev_parent.node.children(&self.prev.graph).nth(index)?;
+                Some(Id {
+                    widget: self.prev.graph[node].data,
+                    node,
+                })
+            }
+        }
+    }
+
+    // Try to reuse the subtree of the previous build pass if the widget is memoized.
+    fn reuse(&mut self, widget: &Widget, prev_id: Option<Id>) -> bool {
+        match prev_id {
+            Some(prev_id)
+                if widget.memo(&**self.prev.widgets.get(prev_id.widget.0))
+                    && !self.prev_states_changed(prev_id) =>
+            {
+                self.clone().copy_prev(prev_id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Any state of the previous subtree has been modified since the last build pass.
+    fn prev_states_changed(&self, prev_id: Id) -> bool {
+        !self.states.changed.is_empty()
+            && prev_id.node.descendants(&self.prev.graph).any(|node| {
+                match self.prev.keys[self.prev.graph[node].data] {
+                    Some(key) => self.states.changed.contains(&key),
+                    None => false,
+                }
+            })
+    }
+
+    fn copy_prev(&mut self, prev_id: Id) {
+        let id = self.new_widget();
+        let key = self.prev.keys[prev_id.widget];
+
+        if key.is_some() {
+            self.intern(id.widget);
+        }
+
+        let widget = self.prev.widgets.get(prev_id.widget.0).clone();
+        assert_eq!(id.widget.0, self.widgets.push(widget));
+        assert_eq!(id.widget.0, self.keys.push(key));
+        assert_eq!(id.widget.0, self.layouts.push(LayoutNode::None));
+
+        self.append_child(self.id.node, id.node);
+        self.id = id;
+
+        if key.is_some() {
+            // Effects of the reused widget are still alive.
+            let key = self.state_key();
+            self.states.retain_effects(key);
+        }
+
+        for child in prev_id.node.children(&self.prev.graph) {
+            let child_id = Id {
+                widget: self.prev.graph[child].data,
+                node: child,
+            };
+            self.clone().copy_prev(child_id);
+        }
+    }
+
     pub unsafe fn add<W: Widget>(&mut self, widget: W, global_key: Option<Key>) {
+        let prev_id = self.prev_child(global_key);
+        if self.reuse(&widget, prev_id) {
+            return;
+        }
+
         let id = self.new_widget();
 
         if let Some(_) = global_key {
-            let prev = *self
-                .states
-                .prev_interns
-                .get(&id.widget)
-                .unwrap_or(&id.widget);
-            self.states.next_interns.insert(id.widget, prev);
+            self.intern(id.widget);
         }
 
-        assert_eq!(id.widget.0, self.widgets.push(Box::new(widget)));
+        assert_eq!(id.widget.0, self.widgets.push(Rc::new(widget)));
         assert_eq!(id.widget.0, self.keys.push(global_key));
         assert_eq!(id.widget.0, self.layouts.push(LayoutNode::None));
 
         self.append_child(self.id.node, id.node);
         self.id = id;
+        self.prev_id = prev_id;
+        self.state_index = 0;
         self.build();
     }
 
     pub unsafe fn add_view(&mut self, view: &View) {
+        let prev_id = self.prev_child(None);
+        if self.reuse(&*view.0, prev_id) {
+            return;
+        }
+
         let id = self.new_widget();
 
-        let ref_view = view::ViewInner::Ref(id);
-        let owned = std::mem::replace(&mut *view.0.borrow_mut(), ref_view);
-        match owned {
-            view::ViewInner::Owned(widget) => {
-                assert_eq!(id.widget.0, self.widgets.push(widget));
-                assert_eq!(id.widget.0, self.layouts.push(LayoutNode::None));
-            }
-            view::ViewInner::Ref(_) => panic!(),
-        }
+        assert_eq!(id.widget.0, self.widgets.push(view.0.clone()));
+        assert_eq!(id.widget.0, self.keys.push(None));
+        assert_eq!(id.widget.0, self.layouts.push(LayoutNode::None));
 
         self.append_child(self.id.node, id.node);
         self.id = id;
+        self.prev_id = prev_id;
+        self.state_index = 0;
         self.build();
     }
 
-    pub fn create_state<S, F>(&mut self, default: F) -> StateInternal
+    /// Create or fetch the next state of the widget.
+    ///
+    /// Multiple states can be created per widget, these are identified by call order
+    /// and therefore must be created in the same order in each build pass.
+    /// Panics if the widget has been added without a key.
+    pub fn create_state<S, F>(&mut self, default: F) -> StateRef
+    where
+        F: FnOnce() -> S,
+        S: 'static,
+    {
+        let slot = StateSlot::Index(self.state_index);
+        self.state_index += 1;
+        self.create_state_slot(slot, default, |_| None)
+    }
+
+    /// Create or fetch the next state of the widget, migrating states of a different type.
+    ///
+    /// If the existing state has a different type (e.g after changing the state struct during
+    /// hot reloading) `migrate` receives the old state. Returning `None` resets the state
+    /// to `default`. See `migrate_from` for `From` based migrations.
+    pub fn create_state_migrate<S, F, M>(&mut self, default: F, migrate: M) -> StateRef
     where
         F: FnOnce() -> S,
+        M: FnOnce(Box<Any>) -> Option<S>,
+        S: 'static,
+    {
+        let slot = StateSlot::Index(self.state_index);
+        self.state_index += 1;
+        self.create_state_slot(slot, default, migrate)
+    }
+
+    /// Create or fetch a state of the widget identified by name.
+    pub fn create_named_state<S, F>(&mut self, name: &'static str, default: F) -> StateRef
+    where
+        F: FnOnce() -> S,
+        S: 'static,
+    {
+        self.create_state_slot(StateSlot::Name(name), default, |_| None)
+    }
+
+    fn create_state_slot<S, F, M>(&mut self, slot: StateSlot, default: F, migrate: M) -> StateRef
+    where
+        F: FnOnce() -> S,
+        M: FnOnce(Box<Any>) -> Option<S>,
         S: 'static,
     {
         let state_ty = TypeId::of::<S>();
-        let key = self.keys[self.id.widget].unwrap();
-        let query_key = match key {
-            Key::Global(_) => Some(key),
-            Key::Local { parent, local } => {
-                let orig_parent_id = self.states.next_interns.get(&parent);
-                if let Some(orig_parent) = orig_parent_id {
-                    Some(Key::Local {
-                        parent: *orig_parent,
-                        local,
-                    })
-                } else {
-                    Some(key)
-                }
-            }
-        };
+        let key = self.state_key();
+        let generation = self.generation;
 
-        if let Some(key) = query_key {
-            match self.states.states.entry(key) {
-                Entry::Occupied(mut value) => {
-                    if value.get().ty != state_ty {
-                        *value.get_mut() = StateStorage {
-                            ty: state_ty,
-                            data: Rc::new(RefCell::new(Box::new(default()))),
-                        };
-                    }
+        #[cfg(feature = "persist")]
+        self.states.reset_saved::<S>(key, slot);
+
+        match self.states.states.entry((key, slot)) {
+            Entry::Occupied(mut value) => {
+                if value.get().ty != state_ty {
+                    let prev_ty = value.get().ty_name;
+                    let prev =
+                        std::mem::replace(value.get_mut(), StateStorage::new((), generation));
 
-                    value.get().data.clone()
+                    // Migration requires sole ownership of the previous state.
+                    let migrated = prev.data.try_unwrap().and_then(migrate);
+                    let (state, kind) = match migrated {
+                        Some(state) => (state, StateEventKind::Migrated),
+                        None => (default(), StateEventKind::Reset),
+                    };
+
+                    *value.get_mut() = StateStorage::new(state, generation);
+                    self.states.events.push(StateEvent {
+                        kind,
+                        key,
+                        slot: slot.into(),
+                        prev_ty: prev_ty.to_string(),
+                        ty: value.get().ty_name.to_string(),
+                    });
                 }
-                Entry::Vacant(entry) => entry
-                    .insert(StateStorage {
-                        ty: state_ty,
-                        data: Rc::new(RefCell::new(Box::new(default()))),
-                    })
-                    .data
-                    .clone(),
+
+                value.get().data.clone()
             }
-        } else {
-            self.states.states.insert(
-                key,
-                StateStorage {
-                    ty: state_ty,
-                    data: Rc::new(RefCell::new(Box::new(default()))),
-                },
-            );
-            self.states.states[&key].data.clone()
+            Entry::Vacant(entry) => entry
+                .insert(StateStorage::new(default(), generation))
+                .data
+                .clone(),
+        }
+    }
+
+    // Key of the current widget used for looking up states and effects.
+    fn state_key(&self) -> Key {
+        let key = self.keys[self.id.widget]
+            .expect("states, memos and effects require a keyed widget (`Build::add` with a key)");
+        self.states.intern_key(key)
+    }
+
+    /// Set the code generation of states and effects created by the widget and its descendants.
+    ///
+    /// Defaults to the generation of the parent, see `FehUI::set_generation`.
+    pub fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
+    /// Current time of the UI, see `FehUI::set_time`.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Cache a derived value of the widget, only recomputed if `deps` changed.
+    ///
+    /// Memos are stored as widget states and share the call order with `create_state`.
+    pub fn use_memo<D, T, F>(&mut self, deps: D, compute: F) -> Rc<T>
+    where
+        D: PartialEq + 'static,
+        T: 'static,
+        F: FnOnce(&D) -> T,
+    {
+        let state = self.create_state(|| Memo::<D, T> {
+            deps: None,
+            value: None,
+        });
+        let mut state = state.borrow_mut();
+        let memo = state.downcast_mut::<Memo<D, T>>().unwrap();
+
+        if memo.deps.as_ref() != Some(&deps) {
+            memo.value = Some(Rc::new(compute(&deps)));
+            memo.deps = Some(deps);
+        }
+
+        memo.value.clone().unwrap()
+    }
+
+    /// Run a side effect after the current build pass if `deps` changed.
+    ///
+    /// The effect returns a cleanup function, which will be executed before the effect
+    /// runs again or when the widget gets unmounted. Effects share the call order
+    /// with `create_state`.
+    pub fn use_effect<D, F, C>(&mut self, deps: D, effect: F)
+    where
+        D: PartialEq + 'static,
+        F: FnOnce() -> C + 'static,
+        C: FnOnce() + 'static,
+    {
+        let key = (self.state_key(), StateSlot::Index(self.state_index));
+        self.state_index += 1;
+
+        let generation = self.generation;
+        let storage = self.states.effects.entry(key).or_insert(EffectStorage {
+            deps: None,
+            cleanup: None,
+            used: false,
+            generation,
+        });
+        storage.used = true;
+
+        let changed = match storage.deps {
+            Some(ref prev) => prev.downcast_ref::<D>() != Some(&deps),
+            None => true,
+        };
+        if changed {
+            storage.deps = Some(Box::new(deps));
+            storage.generation = generation;
+            self.states
+                .pending_effects
+                .push((key, Box::new(move || Box::new(effect()) as Box<FnOnce()>)));
         }
     }
 }
@@ -425,10 +837,112 @@ impl WidgetIdGen {
     }
 }
 
-#[derive(Debug)]
+/// Identifies one of multiple states of a widget.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StateSlot {
+    /// n-th state created via `Build::create_state` during the build of the widget.
+    Index(usize),
+    /// State created via `Build::create_named_state`.
+    Name(&'static str),
+}
+
+/// Owned version of `StateSlot`, the names might belong to unloaded libraries.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SlotName {
+    Index(usize),
+    Name(String),
+}
+
+impl From<StateSlot> for SlotName {
+    fn from(slot: StateSlot) -> Self {
+        match slot {
+            StateSlot::Index(i) => SlotName::Index(i),
+            StateSlot::Name(name) => SlotName::Name(name.to_string()),
+        }
+    }
+}
+
+/// `From` based state migration for `Build::create_state_migrate`.
+pub fn migrate_from<T: 'static, S: From<T>>(prev: Box<Any>) -> Option<S> {
+    prev.downcast::<T>().ok().map(|prev| S::from(*prev))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StateEventKind {
+    /// State has been replaced by its default value.
+    Reset,
+    /// State has been converted by the migration hook.
+    Migrated,
+}
+
+/// Diagnostic event emitted when an existing state changed its type.
+#[derive(Clone, Debug)]
+pub struct StateEvent {
+    pub kind: StateEventKind,
+    pub key: Key,
+    // Owned slot and type names, these might belong to unloaded libraries.
+    pub slot: SlotName,
+    pub prev_ty: String,
+    pub ty: String,
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
 struct StateStorage {
     ty: TypeId,
-    data: StateInternal,
+    ty_name: &'static str,
+    data: StateRef,
+    generation: u64,
+    #[cfg(feature = "persist")]
+    #[derivative(Debug = "ignore")]
+    save: Option<persist::SaveFn>, // only set for persistent states
+}
+
+impl StateStorage {
+    fn new<S: 'static>(state: S, generation: u64) -> Self {
+        StateStorage {
+            ty: TypeId::of::<S>(),
+            ty_name: std::any::type_name::<S>(),
+            data: StateRef::new(Box::new(state)),
+            generation,
+            #[cfg(feature = "persist")]
+            save: None,
+        }
+    }
+}
+
+// Widget tree of the previous build pass.
+#[derive(Debug)]
+struct PrevTree {
+    root: Option<Id>,
+    generation: Option<u64>,
+    graph: Graph,
+    graph_map: GraphMap,
+    widgets: Widgets,
+    keys: Keys,
+    key_map: HashMap<Key, WidgetId>, // Map: Global Key -> Instance
+}
+impl PrevTree {
+    pub fn new() -> Self {
+        PrevTree {
+            root: None,
+            generation: None,
+            graph: Graph::new(),
+            graph_map: GraphMap::new(),
+            widgets: Widgets::new(),
+            keys: Keys::new(),
+            key_map: HashMap::new(),
+        }
+    }
+
+    fn update_key_map(&mut self) {
+        self.key_map.clear();
+        for i in 0..self.keys.len() {
+            if let Some(key @ Key::Global(_)) = *self.keys.get(i) {
+                self.key_map.insert(key, WidgetId(i));
+            }
+        }
+    }
 }
 
 type InternMap = HashMap<WidgetId, WidgetId>;
@@ -440,9 +954,18 @@ type InternMap = HashMap<WidgetId, WidgetId>;
 // `prev_intern` will contain the mapping information for the previous build pass.
 // `next_intern` will contain the new connections in the current build pass.
 // Afterwards `next_inter` will be replace the contents of `prev_intern`.
-#[derive(Debug)]
+#[derive(Derivative)]
+#[derivative(Debug)]
 struct StateCache {
-    states: HashMap<Key, StateStorage>,
+    states: HashMap<(Key, StateSlot), StateStorage>,
+    #[derivative(Debug = "ignore")]
+    effects: HashMap<(Key, StateSlot), EffectStorage>,
+    #[derivative(Debug = "ignore")]
+    pending_effects: Vec<((Key, StateSlot), PendingEffect)>,
+    events: Vec<StateEvent>,
+    changed: HashSet<Key>, // keys of states modified since the last build pass
+    #[cfg(feature = "persist")]
+    saved: HashMap<(Key, SlotName), persist::SavedState>, // restored, not yet created
     prev_interns: InternMap,
     next_interns: InternMap,
 }
@@ -450,8 +973,684 @@ impl StateCache {
     pub fn new() -> Self {
         StateCache {
             states: HashMap::new(),
+            effects: HashMap::new(),
+            pending_effects: Vec::new(),
+            events: Vec::new(),
+            changed: HashSet::new(),
+            #[cfg(feature = "persist")]
+            saved: HashMap::new(),
             prev_interns: InternMap::new(),
             next_interns: InternMap::new(),
         }
     }
+
+    // Key used for looking up states of a widget with the given key.
+    fn intern_key(&self, key: Key) -> Key {
+        match key {
+            Key::Global(_) => key,
+            Key::Local { parent, local } => match self.next_interns.get(&parent) {
+                Some(orig_parent) => Key::Local {
+                    parent: *orig_parent,
+                    local,
+                },
+                None => key,
+            },
+        }
+    }
+
+    fn collect_changed(&mut self) {
+        self.changed = self
+            .states
+            .iter()
+            .filter(|(_, state)| state.data.take_changed())
+            .map(|(&(key, _), _)| key)
+            .collect();
+    }
+
+    fn reset_changed(&mut self) {
+        for state in self.states.values() {
+            state.data.take_changed();
+        }
+    }
+
+    fn retain_effects(&mut self, key: Key) {
+        for (_, effect) in self.effects.iter_mut().filter(|(k, _)| k.0 == key) {
+            effect.used = true;
+        }
+    }
+
+    // Execute the effects of the last build pass.
+    //
+    // Effects of widgets which haven't been built in the last pass are considered as unmounted
+    // and only their cleanup will be executed.
+    fn discard_pending_effects(&mut self) {
+        for (key, _) in self.pending_effects.drain(..) {
+            // Run the effect again on the next build, even if the deps are unchanged.
+            if let Some(storage) = self.effects.get_mut(&key) {
+                storage.deps = None;
+            }
+        }
+        for effect in self.effects.values_mut() {
+            effect.used = false;
+        }
+    }
+
+    fn flush_effects(&mut self) {
+        let unmounted = self
+            .effects
+            .iter()
+            .filter(|(_, effect)| !effect.used)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        for key in unmounted {
+            if let Some(cleanup) = self.effects.remove(&key).and_then(|effect| effect.cleanup) {
+                cleanup();
+            }
+        }
+
+        for (key, effect) in self.pending_effects.drain(..) {
+            if let Some(storage) = self.effects.get_mut(&key) {
+                if let Some(cleanup) = storage.cleanup.take() {
+                    cleanup();
+                }
+                storage.cleanup = Some(effect());
+            }
+        }
+
+        for effect in self.effects.values_mut() {
+            effect.used = false;
+        }
+    }
+}
+
+struct Memo<D, T> {
+    deps: Option<D>,
+    value: Option<Rc<T>>,
+}
+
+type PendingEffect = Box<FnOnce() -> Box<FnOnce()>>;
+
+struct EffectStorage {
+    deps: Option<Box<Any>>,
+    cleanup: Option<Box<FnOnce()>>,
+    used: bool, // effect has been requested in the current build pass
+    generation: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    thread_local! {
+        static COUNTER_BUILDS: Cell<usize> = Cell::new(0);
+        static EFFECT_LOG: RefCell<Vec<String>> = RefCell::new(Vec::new());
+        static MEMO_COMPUTES: Cell<usize> = Cell::new(0);
+        static MEMO_VALUE: Cell<u32> = Cell::new(0);
+    }
+
+    const COUNTER_KEY: Key = Key::Global(1);
+
+    #[derive(Debug)]
+    struct Root;
+
+    impl Widget for Root {
+        fn build(&self, mut build: Build) {
+            unsafe { build.add(Memoized, None) };
+        }
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Pass
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Memoized;
+
+    impl Widget for Memoized {
+        fn build(&self, mut build: Build) {
+            unsafe { build.add(Counter, Some(COUNTER_KEY)) };
+        }
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Pass
+        }
+
+        fn memo(&self, prev: &Widget) -> bool {
+            memo_eq(self, prev)
+        }
+    }
+
+    #[derive(Debug)]
+    struct Counter;
+
+    impl Widget for Counter {
+        fn build(&self, mut build: Build) {
+            build.create_state(|| 0usize);
+            COUNTER_BUILDS.with(|builds| builds.set(builds.get() + 1));
+        }
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Pass
+        }
+    }
+
+    fn counter_builds() -> usize {
+        COUNTER_BUILDS.with(Cell::get)
+    }
+
+    #[test]
+    fn memo_rebuilds_changed_states() {
+        let mut ui = FehUI::new();
+        ui.build(Root);
+        assert_eq!(counter_builds(), 1);
+
+        assert!(ui.rebuild());
+        assert_eq!(counter_builds(), 1);
+
+        let state = ui.state(COUNTER_KEY, StateSlot::Index(0)).unwrap();
+        *state.borrow_mut().downcast_mut::<usize>().unwrap() += 1;
+        assert!(ui.rebuild());
+        assert_eq!(counter_builds(), 2);
+
+        assert!(ui.rebuild());
+        assert_eq!(counter_builds(), 2);
+    }
+
+    #[derive(Debug)]
+    struct Leaf(usize);
+
+    impl Widget for Leaf {
+        fn build(&self, _: Build) {}
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Pass
+        }
+    }
+
+    // Root widget adding its children via `IntoChildren`.
+    #[derive(Debug)]
+    struct Fragment(fn(&mut Build));
+
+    impl Widget for Fragment {
+        fn build(&self, mut build: Build) {
+            (self.0)(&mut build);
+        }
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Pass
+        }
+    }
+
+    fn leaves(ui: &FehUI) -> Vec<usize> {
+        ui.children(ui.root.unwrap())
+            .into_iter()
+            .map(|id| ui.widget(id).as_any().downcast_ref::<Leaf>().unwrap().0)
+            .collect()
+    }
+
+    fn build_fragment(build: fn(&mut Build)) -> Vec<usize> {
+        let mut ui = FehUI::new();
+        ui.build(Fragment(build));
+        leaves(&ui)
+    }
+
+    #[test]
+    fn vec_children() {
+        let children = build_fragment(|build| {
+            vec![View::from(Leaf(0)), View::from(Leaf(1))].add_children(build)
+        });
+        assert_eq!(children, vec![0, 1]);
+    }
+
+    #[test]
+    fn option_children() {
+        let some = build_fragment(|build| Some(Leaf(0)).add_children(build));
+        assert_eq!(some, vec![0]);
+        let none = build_fragment(|build| None::<Leaf>.add_children(build));
+        assert_eq!(none, vec![]);
+    }
+
+    #[test]
+    fn iterator_children() {
+        let children = build_fragment(|build| Children((0..3).map(Leaf)).add_children(build));
+        assert_eq!(children, vec![0, 1, 2]);
+    }
+
+    #[derive(Debug)]
+    struct Slots;
+
+    impl Widget for Slots {
+        fn build(&self, mut build: Build) {
+            build.create_state(|| 1u32);
+            build.create_named_state("name", || "named".to_string());
+            build.create_state(|| 2u32);
+        }
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Pass
+        }
+    }
+
+    fn state<S: Clone + 'static>(ui: &FehUI, key: Key, slot: StateSlot) -> S {
+        let state = ui.state(key, slot).unwrap();
+        let state = state.borrow();
+        state.downcast_ref::<S>().unwrap().clone()
+    }
+
+    #[test]
+    fn state_slots() {
+        let key = GlobalKeygen::ROOT_KEY;
+        let mut ui = FehUI::new();
+        ui.build(Slots);
+        assert_eq!(state::<u32>(&ui, key, StateSlot::Index(0)), 1);
+        assert_eq!(state::<u32>(&ui, key, StateSlot::Index(1)), 2);
+        assert_eq!(state::<String>(&ui, key, StateSlot::Name("name")), "named");
+
+        let slot = ui.state(key, StateSlot::Index(1)).unwrap();
+        *slot.borrow_mut().downcast_mut::<u32>().unwrap() = 3;
+        assert!(ui.rebuild());
+        assert_eq!(state::<u32>(&ui, key, StateSlot::Index(0)), 1);
+        assert_eq!(state::<u32>(&ui, key, StateSlot::Index(1)), 3);
+        assert_eq!(state::<String>(&ui, key, StateSlot::Name("name")), "named");
+        assert!(ui.take_state_events().is_empty());
+    }
+
+    #[derive(Debug)]
+    struct EffectParent {
+        deps: u32,
+        mounted: bool,
+    }
+
+    impl Widget for EffectParent {
+        fn build(&self, mut build: Build) {
+            if self.mounted {
+                unsafe { build.add(EffectChild(self.deps), Some(Key::Global(2))) };
+            }
+        }
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Pass
+        }
+    }
+
+    #[derive(Debug)]
+    struct EffectChild(u32);
+
+    impl Widget for EffectChild {
+        fn build(&self, mut build: Build) {
+            let deps = self.0;
+            build.use_effect(deps, move || {
+                log_effect(format!("run {}", deps));
+                move || log_effect(format!("cleanup {}", deps))
+            });
+        }
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Pass
+        }
+    }
+
+    fn log_effect(entry: String) {
+        EFFECT_LOG.with(|log| log.borrow_mut().push(entry));
+    }
+
+    fn take_effect_log() -> Vec<String> {
+        EFFECT_LOG.with(|log| log.borrow_mut().drain(..).collect())
+    }
+
+    #[test]
+    fn effect_cleanup() {
+        let mut ui = FehUI::new();
+        ui.build(EffectParent {
+            deps: 1,
+            mounted: true,
+        });
+        assert_eq!(take_effect_log(), vec!["run 1"]);
+
+        ui.build(EffectParent {
+            deps: 1,
+            mounted: true,
+        });
+        assert!(take_effect_log().is_empty());
+
+        ui.build(EffectParent {
+            deps: 2,
+            mounted: true,
+        });
+        assert_eq!(take_effect_log(), vec!["cleanup 1", "run 2"]);
+
+        ui.build(EffectParent {
+            deps: 2,
+            mounted: false,
+        });
+        assert_eq!(take_effect_log(), vec!["cleanup 2"]);
+    }
+
+    // Runs an effect and panics afterwards if the flag is set.
+    #[derive(Debug)]
+    struct Aborted(bool);
+
+    impl Widget for Aborted {
+        fn build(&self, mut build: Build) {
+            build.use_effect(1, || {
+                log_effect("run".to_string());
+                || log_effect("cleanup".to_string())
+            });
+            if self.0 {
+                panic!("build aborted");
+            }
+        }
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Pass
+        }
+    }
+
+    #[test]
+    fn effects_of_aborted_builds() {
+        let mut ui = FehUI::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            ui.build(Aborted(true));
+        }));
+        assert!(result.is_err());
+        assert!(take_effect_log().is_empty());
+
+        ui.build(Aborted(false));
+        assert_eq!(take_effect_log(), vec!["run"]);
+        ui.build(Aborted(false));
+        assert!(take_effect_log().is_empty());
+    }
+
+    #[test]
+    fn effect_cleanup_on_drop() {
+        let mut ui = FehUI::new();
+        ui.build(EffectParent {
+            deps: 1,
+            mounted: true,
+        });
+        assert_eq!(take_effect_log(), vec!["run 1"]);
+
+        drop(ui);
+        assert_eq!(take_effect_log(), vec!["cleanup 1"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "require a keyed widget")]
+    fn unkeyed_state() {
+        let mut ui = FehUI::new();
+        ui.build(Fragment(|build| unsafe { build.add(Counter, None) }));
+    }
+
+    #[derive(Debug)]
+    struct Doubled(u32);
+
+    impl Widget for Doubled {
+        fn build(&self, mut build: Build) {
+            let value = build.use_memo(self.0, |value| {
+                MEMO_COMPUTES.with(|computes| computes.set(computes.get() + 1));
+                value * 2
+            });
+            MEMO_VALUE.with(|memo| memo.set(*value));
+        }
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Pass
+        }
+    }
+
+    fn memo_result() -> (usize, u32) {
+        (MEMO_COMPUTES.with(Cell::get), MEMO_VALUE.with(Cell::get))
+    }
+
+    #[test]
+    fn use_memo_deps() {
+        let mut ui = FehUI::new();
+        ui.build(Doubled(1));
+        assert_eq!(memo_result(), (1, 2));
+
+        ui.build(Doubled(1));
+        assert_eq!(memo_result(), (1, 2));
+
+        ui.build(Doubled(3));
+        assert_eq!(memo_result(), (2, 6));
+
+        assert!(ui.rebuild());
+        assert_eq!(memo_result(), (2, 6));
+    }
+
+    #[derive(Debug)]
+    enum Versioned {
+        V1,
+        V2,
+        V3,
+    }
+
+    impl Widget for Versioned {
+        fn build(&self, mut build: Build) {
+            match self {
+                Versioned::V1 => {
+                    build.create_state(|| 5u32);
+                }
+                Versioned::V2 => {
+                    build.create_state_migrate(|| 0u64, migrate_from::<u32, u64>);
+                }
+                Versioned::V3 => {
+                    build.create_state_migrate(|| "reset".to_string(), |_| None);
+                }
+            }
+        }
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Pass
+        }
+    }
+
+    #[test]
+    fn state_migration() {
+        let key = GlobalKeygen::ROOT_KEY;
+        let slot = StateSlot::Index(0);
+        let mut ui = FehUI::new();
+        ui.build(Versioned::V1);
+        assert!(ui.take_state_events().is_empty());
+
+        ui.build(Versioned::V2);
+        assert_eq!(state::<u64>(&ui, key, slot), 5);
+        let events = ui.take_state_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, StateEventKind::Migrated);
+        assert_eq!(events[0].key, key);
+        assert_eq!(events[0].slot, SlotName::Index(0));
+        assert_eq!((&*events[0].prev_ty, &*events[0].ty), ("u32", "u64"));
+        assert!(ui.take_state_events().is_empty());
+
+        ui.build(Versioned::V3);
+        assert_eq!(state::<String>(&ui, key, slot), "reset");
+        let events = ui.take_state_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, StateEventKind::Reset);
+        assert_eq!(events[0].prev_ty, "u64");
+    }
+
+    #[cfg(feature = "persist")]
+    #[derive(Debug)]
+    enum Persisted {
+        V1,
+        V2,
+    }
+
+    #[cfg(feature = "persist")]
+    impl Widget for Persisted {
+        fn build(&self, mut build: Build) {
+            match self {
+                Persisted::V1 => {
+                    build.create_persistent_state(|| 1u32);
+                    build.create_state(|| 2u32);
+                }
+                Persisted::V2 => {
+                    build.create_persistent_state(|| "default".to_string());
+                    build.create_state(|| 3u32);
+                }
+            }
+        }
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Pass
+        }
+    }
+
+    #[cfg(feature = "persist")]
+    #[test]
+    fn restore_resets() {
+        let key = GlobalKeygen::ROOT_KEY;
+        let mut ui = FehUI::new();
+        ui.build(Persisted::V1);
+        let saved = ui.save_states();
+        ui.restore_states(saved);
+        ui.build(Persisted::V2);
+
+        assert_eq!(state::<String>(&ui, key, StateSlot::Index(0)), "default");
+        assert_eq!(state::<u32>(&ui, key, StateSlot::Index(1)), 3);
+        let mut events = ui.take_state_events();
+        events.sort_by_key(|event| event.slot == SlotName::Index(1));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, StateEventKind::Reset);
+        assert_eq!(events[0].slot, SlotName::Index(0));
+        assert_eq!(events[0].prev_ty, "u32");
+        assert_eq!(events[1].kind, StateEventKind::Reset);
+        assert_eq!(events[1].slot, SlotName::Index(1));
+    }
+
+    #[cfg(feature = "persist")]
+    #[test]
+    fn unclaimed_saved_states_are_dropped() {
+        let key = GlobalKeygen::ROOT_KEY;
+        let mut ui = FehUI::new();
+        ui.build(Persisted::V1);
+        let state = ui.state(key, StateSlot::Index(0)).unwrap();
+        *state.borrow_mut().downcast_mut::<u32>().unwrap() = 5;
+        drop(state);
+        let saved = ui.save_states();
+
+        ui.restore_states(saved.clone());
+        ui.build(Persisted::V1);
+        assert_eq!(state::<u32>(&ui, key, StateSlot::Index(0)), 5);
+        let events = ui.take_state_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].slot, SlotName::Index(1));
+
+        ui.save_states();
+        ui.restore_states(saved);
+        ui.build(Leaf(0));
+        ui.build(Persisted::V1);
+        assert_eq!(state::<u32>(&ui, key, StateSlot::Index(0)), 1);
+        assert!(ui.take_state_events().is_empty());
+    }
+
+    #[derive(Default)]
+    struct EngineCounts {
+        nodes: Cell<usize>,
+        removed: Cell<usize>,
+    }
+
+    impl EngineCounts {
+        fn get(&self) -> (usize, usize) {
+            (self.nodes.get(), self.removed.get())
+        }
+    }
+
+    // Layout engine recording how many nodes have been created and removed.
+    struct CountingEngine {
+        inner: StretchEngine,
+        counts: Rc<EngineCounts>,
+    }
+
+    impl LayoutEngine for CountingEngine {
+        fn new_node(&mut self, style: &stretch::style::Style) -> EngineNode {
+            self.counts.nodes.set(self.counts.nodes.get() + 1);
+            self.inner.new_node(style)
+        }
+
+        fn add_child(&mut self, parent: EngineNode, child: EngineNode) {
+            self.inner.add_child(parent, child)
+        }
+
+        fn remove(&mut self, node: EngineNode) {
+            self.counts.removed.set(self.counts.removed.get() + 1);
+            self.inner.remove(node)
+        }
+
+        fn compute_layout(&mut self, root: EngineNode, width: f32, height: f32) {
+            self.inner.compute_layout(root, width, height)
+        }
+
+        fn size(&self, node: EngineNode) -> (f32, f32) {
+            self.inner.size(node)
+        }
+
+        fn position(&self, node: EngineNode) -> (f32, f32) {
+            self.inner.position(node)
+        }
+    }
+
+    // Flex node with the given number of flex children.
+    #[derive(Debug)]
+    struct Flexed(usize);
+
+    impl Widget for Flexed {
+        fn build(&self, mut build: Build) {
+            for _ in 0..self.0 {
+                unsafe { build.add(Flexed(0), None) };
+            }
+        }
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Flex(Default::default())
+        }
+    }
+
+    #[test]
+    fn layout_nodes() {
+        let counts = Rc::new(EngineCounts::default());
+        let mut ui = FehUI::with_engine(CountingEngine {
+            inner: StretchEngine::new(),
+            counts: counts.clone(),
+        });
+        ui.build(Flexed(2));
+        assert_eq!(counts.get(), (0, 0));
+
+        ui.layout(100.0, 100.0);
+        assert_eq!(counts.get(), (3, 0));
+
+        // Resizing only recomputes the existing nodes.
+        ui.layout(200.0, 50.0);
+        assert_eq!(counts.get(), (3, 0));
+
+        ui.build(Flexed(1));
+        assert_eq!(counts.get(), (3, 3));
+        ui.layout(200.0, 50.0);
+        assert_eq!(counts.get(), (5, 3));
+    }
+
+    #[derive(Debug)]
+    struct Stored(View);
+
+    impl Widget for Stored {
+        fn build(&self, mut build: Build) {
+            unsafe { build.add_view(&self.0) };
+        }
+
+        fn layout(&self, _: Layouter) -> Layout {
+            Layout::Pass
+        }
+    }
+
+    #[test]
+    fn rebuild_stored_views() {
+        let mut ui = FehUI::new();
+        ui.build(Stored(Leaf(7).into()));
+        assert!(ui.rebuild());
+        assert!(ui.rebuild());
+        assert_eq!(leaves(&ui), vec![7]);
+    }
 }
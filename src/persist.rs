@@ -0,0 +1,163 @@
+//! Serialization of widget states, e.g. for keeping states alive across hot reloads.
+//!
+//! States created via `Build::create_persistent_state` can be saved with `FehUI::save_states`
+//! and will be deserialized into the (possibly changed) state type on the next creation after
+//! `FehUI::restore_states`.
+
+use crate::{
+    Build, FehUI, Key, SlotName, StateCache, StateEvent, StateEventKind, StateRef, StateSlot,
+    StateStorage,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+
+pub(crate) type SaveFn = fn(&Any) -> Option<String>;
+
+fn save_state<S: Serialize + 'static>(state: &Any) -> Option<String> {
+    state
+        .downcast_ref::<S>()
+        .and_then(|state| serde_json::to_string(state).ok())
+}
+
+// Saved state, `data` is only set for persistent states.
+#[derive(Clone, Debug)]
+pub(crate) struct SavedState {
+    ty: String,
+    data: Option<String>,
+}
+
+/// Serialized persistent states of a `FehUI` instance.
+///
+/// Non-persistent states are recorded as well, recreating these emits a reset `StateEvent`.
+#[derive(Clone, Debug, Default)]
+pub struct SavedStates(HashMap<(Key, SlotName), SavedState>);
+
+impl SavedStates {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FehUI {
+    /// Serialize all persistent states and remove all states and effects.
+    ///
+    /// Needs to be called before unloading the code the states belong to. Pending effect
+    /// cleanups will be executed. Other states are reset to their default on the next creation
+    /// after `restore_states`, emitting a `StateEvent`.
+    pub fn save_states(&mut self) -> SavedStates {
+        self.save_states_filter(|_| true)
+    }
+
+    /// Like `save_states`, but only for states and effects of the given code generations.
+    ///
+    /// See `Build::set_generation` for attributing states to a generation.
+    pub fn save_generations(&mut self, generations: &HashSet<u64>) -> SavedStates {
+        self.save_states_filter(|generation| generations.contains(&generation))
+    }
+
+    fn save_states_filter<F: Fn(u64) -> bool>(&mut self, filter: F) -> SavedStates {
+        let keys = self
+            .states
+            .states
+            .iter()
+            .filter(|(_, storage)| filter(storage.generation))
+            .map(|(&key, _)| key)
+            .collect::<Vec<_>>();
+
+        let mut saved = HashMap::new();
+        for (key, slot) in keys {
+            let storage = self.states.states.remove(&(key, slot)).unwrap();
+            let data = storage.save.and_then(|save| save(&**storage.data.borrow()));
+            let state = SavedState {
+                ty: storage.ty_name.to_string(),
+                data,
+            };
+            saved.insert((key, SlotName::from(slot)), state);
+        }
+
+        let effects = self
+            .states
+            .effects
+            .iter()
+            .filter(|(_, effect)| filter(effect.generation))
+            .map(|(&key, _)| key)
+            .collect::<Vec<_>>();
+        for key in effects {
+            if let Some(cleanup) = self.states.effects.remove(&key).unwrap().cleanup {
+                cleanup();
+            }
+        }
+
+        SavedStates(saved)
+    }
+
+    /// Restore saved states, these will be deserialized on the next state creation.
+    ///
+    /// Saved states which aren't created again by the next build pass are dropped.
+    pub fn restore_states(&mut self, states: SavedStates) {
+        self.states.saved.extend(states.0);
+    }
+}
+
+impl Build<'_> {
+    /// Create or fetch the next state of the widget, which will be kept by `FehUI::save_states`.
+    pub fn create_persistent_state<S, F>(&mut self, default: F) -> StateRef
+    where
+        F: FnOnce() -> S,
+        S: Serialize + DeserializeOwned + 'static,
+    {
+        let slot = StateSlot::Index(self.state_index);
+        self.state_index += 1;
+        let key = self.state_key();
+
+        let restored = match self.states.saved.remove(&(key, SlotName::from(slot))) {
+            Some(saved) => {
+                let state = saved
+                    .data
+                    .and_then(|data| serde_json::from_str::<S>(&data).ok());
+                if state.is_none() {
+                    self.states.push_reset::<S>(key, slot, saved.ty);
+                }
+                state
+            }
+            None => None,
+        };
+
+        let state = match restored {
+            Some(state) => {
+                let storage = StateStorage::new(state, self.generation);
+                let data = storage.data.clone();
+                self.states.states.insert((key, slot), storage);
+                data
+            }
+            None => self.create_state_slot(slot, default, |_| None),
+        };
+
+        self.states.states.get_mut(&(key, slot)).unwrap().save = Some(save_state::<S>);
+        state
+    }
+}
+
+impl StateCache {
+    // Drop the saved state of a slot, which is created again without restoring it.
+    pub(crate) fn reset_saved<S: 'static>(&mut self, key: Key, slot: StateSlot) {
+        if self.saved.is_empty() {
+            return;
+        }
+        if let Some(saved) = self.saved.remove(&(key, SlotName::from(slot))) {
+            self.push_reset::<S>(key, slot, saved.ty);
+        }
+    }
+
+    fn push_reset<S: 'static>(&mut self, key: Key, slot: StateSlot, prev_ty: String) {
+        self.events.push(StateEvent {
+            kind: StateEventKind::Reset,
+            key,
+            slot: slot.into(),
+            prev_ty,
+            ty: std::any::type_name::<S>().to_string(),
+        });
+    }
+}
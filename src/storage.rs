@@ -1,87 +1,72 @@
 use super::*;
-use std::cell::UnsafeCell;
+use std::cell::{Cell, UnsafeCell};
 
-const CHUNK_SIZE: usize = 1; // TODO
+const CHUNK_SIZE: usize = 256;
 
+/// Append-only arena with stable element addresses.
+///
+/// Elements are stored in chunks of fixed capacity. A chunk never reallocates,
+/// therefore references returned by `get` stay valid while new elements are pushed.
+/// Elements can only be removed all at once via `clear`, which requires exclusive access.
 #[derive(Derivative)]
-#[derivative(Debug)]
-struct Chunk<T> {
-    data: Box<[T; CHUNK_SIZE]>,
-    len: usize,
-}
-
-#[derive(Derivative)]
-#[derivative(Debug)]
+#[derivative(Debug(bound = ""))]
 pub struct Storage<T> {
-    chunks: UnsafeCell<Vec<Chunk<T>>>,
-    num_elements: UnsafeCell<usize>,
+    #[derivative(Debug = "ignore")]
+    chunks: UnsafeCell<Vec<Vec<T>>>,
+    len: Cell<usize>,
 }
 
 impl<T> Storage<T> {
     pub fn new() -> Self {
         Storage {
             chunks: UnsafeCell::new(Vec::new()),
-            num_elements: UnsafeCell::new(0),
+            len: Cell::new(0),
         }
     }
 
     pub fn clear(&mut self) {
-        let chunks = unsafe { &mut *self.chunks.get() };
-        let num_elems = unsafe { &mut *self.num_elements.get() };
-
-        chunks.clear();
-        *num_elems = 0;
+        // Only drops the initialized elements of each chunk.
+        self.chunks.get_mut().clear();
+        self.len.set(0);
     }
 
     pub fn push(&self, value: T) -> usize {
+        // SAFETY: `Storage` is `!Sync` and no references to the chunk list itself escape,
+        // only references to elements. Pushing into a chunk with spare capacity doesn't
+        // move existing elements, so outstanding element references remain valid.
         let chunks = unsafe { &mut *self.chunks.get() };
-        let num_elems = unsafe { &mut *self.num_elements.get() };
-        let id = *num_elems;
-        let chunk_id = id / CHUNK_SIZE;
-        let data_id = id % CHUNK_SIZE;
-
-        if chunk_id >= chunks.len() {
-            chunks.push(Chunk {
-                data: Box::new(unsafe { std::mem::uninitialized() }),
-                len: 0,
-            });
+        let id = self.len.get();
+
+        if id % CHUNK_SIZE == 0 {
+            chunks.push(Vec::with_capacity(CHUNK_SIZE));
         }
 
-        std::mem::forget(std::mem::replace(
-            &mut chunks[chunk_id].data[data_id],
-            value,
-        ));
-        chunks[chunk_id].len += 1;
-        *num_elems += 1;
+        let chunk = chunks.last_mut().unwrap();
+        debug_assert!(chunk.len() < chunk.capacity());
+        chunk.push(value);
+        self.len.set(id + 1);
 
         id
     }
 
     pub fn len(&self) -> usize {
-        unsafe { *self.num_elements.get() }
+        self.len.get()
     }
 
     pub fn get(&self, idx: usize) -> &T {
         let chunks = unsafe { &*self.chunks.get() };
-        let chunk_id = idx / CHUNK_SIZE;
-        let data_id = idx % CHUNK_SIZE;
-
-        &chunks[chunk_id].data[data_id]
+        &chunks[idx / CHUNK_SIZE][idx % CHUNK_SIZE]
     }
 
-    pub fn get_mut(&self, idx: usize) -> &mut T {
-        let chunks = unsafe { &mut *self.chunks.get() };
-        let chunk_id = idx / CHUNK_SIZE;
-        let data_id = idx % CHUNK_SIZE;
-
-        &mut chunks[chunk_id].data[data_id]
+    pub fn get_mut(&mut self, idx: usize) -> &mut T {
+        &mut self.chunks.get_mut()[idx / CHUNK_SIZE][idx % CHUNK_SIZE]
     }
 }
 
-pub type Widgets = Storage<Box<Widget>>;
+pub type Widgets = Storage<Rc<Widget>>;
 
 impl std::ops::Index<WidgetId> for Widgets {
-    type Output = Box<Widget>;
+    type Output = Rc<Widget>;
     fn index(&self, idx: WidgetId) -> &Self::Output {
         self.get(idx.0)
     }
@@ -101,3 +86,112 @@ impl std::ops::IndexMut<WidgetId> for Keys {
         self.get_mut(idx.0)
     }
 }
+
+// The `unsafe` in `push` and `get` is checked by running these under Miri:
+// `cargo +nightly miri test --lib storage`
+#[cfg(test)]
+mod tests {
+    use super::{Storage, CHUNK_SIZE};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct DropCounter(Rc<Cell<usize>>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn references_survive_pushes() {
+        let storage = Storage::new();
+        for i in 0..CHUNK_SIZE {
+            storage.push(i.to_string());
+        }
+
+        // References into the first and the full last chunk, while new chunks are allocated.
+        let first = storage.get(0);
+        let last = storage.get(CHUNK_SIZE - 1);
+        for i in CHUNK_SIZE..CHUNK_SIZE * 3 + 1 {
+            storage.push(i.to_string());
+            assert_eq!(first, "0");
+            assert_eq!(*last, (CHUNK_SIZE - 1).to_string());
+        }
+
+        assert_eq!(storage.len(), CHUNK_SIZE * 3 + 1);
+        for i in 0..storage.len() {
+            assert_eq!(*storage.get(i), i.to_string());
+        }
+    }
+
+    #[test]
+    fn references_into_partial_chunk() {
+        let storage = Storage::new();
+        for i in 0..CHUNK_SIZE + 1 {
+            storage.push(i.to_string());
+        }
+
+        // The last chunk is only partially filled, pushes write into the same chunk.
+        let partial = storage.get(CHUNK_SIZE);
+        for i in CHUNK_SIZE + 1..CHUNK_SIZE * 2 {
+            storage.push(i.to_string());
+            assert_eq!(*partial, CHUNK_SIZE.to_string());
+        }
+        assert_eq!(
+            *storage.get(CHUNK_SIZE * 2 - 1),
+            (CHUNK_SIZE * 2 - 1).to_string()
+        );
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut storage = Storage::new();
+        for i in 0..CHUNK_SIZE + 1 {
+            storage.push(i);
+        }
+        *storage.get_mut(CHUNK_SIZE) += 1;
+        assert_eq!(*storage.get(CHUNK_SIZE), CHUNK_SIZE + 1);
+    }
+
+    #[test]
+    fn clear_drops_pushed_elements() {
+        let drops = Rc::new(Cell::new(0));
+        let mut storage = Storage::new();
+
+        // Last chunk is only partially filled.
+        let count = CHUNK_SIZE * 2 + CHUNK_SIZE / 2;
+        for _ in 0..count {
+            storage.push(DropCounter(drops.clone()));
+        }
+        assert_eq!(drops.get(), 0);
+
+        storage.clear();
+        assert_eq!(drops.get(), count);
+        assert_eq!(storage.len(), 0);
+
+        // Still usable after clearing, remaining elements are dropped with the storage.
+        storage.push(DropCounter(drops.clone()));
+        assert_eq!(storage.len(), 1);
+        drop(storage);
+        assert_eq!(drops.get(), count + 1);
+    }
+
+    #[test]
+    fn zero_sized() {
+        let mut storage = Storage::new();
+        for _ in 0..CHUNK_SIZE {
+            storage.push(());
+        }
+
+        let first = storage.get(0);
+        assert_eq!(storage.push(()), CHUNK_SIZE);
+        assert_eq!(*first, ());
+        assert_eq!(*storage.get(CHUNK_SIZE), ());
+        assert_eq!(storage.len(), CHUNK_SIZE + 1);
+
+        storage.clear();
+        assert_eq!(storage.len(), 0);
+        assert_eq!(storage.push(()), 0);
+    }
+}
@@ -1,26 +1,16 @@
-use crate::{Id, Widget};
-use std::cell::RefCell;
+use crate::{Build, Widget};
+use std::rc::Rc;
 
+/// Widget stored in another widget, e.g. a child of a container.
+///
+/// The widget is shared with the widget trees the view has been added to, so views can be
+/// built several times (e.g. by `FehUI::rebuild`).
 #[derive(Debug)]
-pub struct View(pub(crate) RefCell<ViewInner>);
-
-pub enum ViewInner {
-    Owned(Box<Widget>),
-    Ref(Id),
-}
-
-impl std::fmt::Debug for ViewInner {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match *self {
-            ViewInner::Owned(_) => write!(f, "View::Owned"),
-            ViewInner::Ref(ref id) => write!(f, "View::Ref({:?})", id),
-        }
-    }
-}
+pub struct View(pub(crate) Rc<Widget>);
 
 impl<W: Widget> From<W> for View {
     fn from(widget: W) -> Self {
-        View(RefCell::new(ViewInner::Owned(Box::new(widget))))
+        View(Rc::new(widget))
     }
 }
 
@@ -34,3 +24,55 @@ macro_rules! views {
     );
     ($($x:expr,)*) => (vec![$($x.into()),*])
 }
+
+/// Children returned from the build function of derived widgets.
+///
+/// Implemented for single widgets, `View`, `Vec<View>`, `Option<_>` and iterators wrapped
+/// into `Children`, allowing widgets to act as fragments without a wrapping container.
+pub trait IntoChildren {
+    fn add_children(self, ctxt: &mut Build);
+}
+
+impl<W: Widget> IntoChildren for W {
+    fn add_children(self, ctxt: &mut Build) {
+        unsafe { ctxt.clone().add(self, None) };
+    }
+}
+
+impl IntoChildren for View {
+    fn add_children(self, ctxt: &mut Build) {
+        unsafe { ctxt.clone().add_view(&self) };
+    }
+}
+
+impl IntoChildren for Vec<View> {
+    fn add_children(self, ctxt: &mut Build) {
+        for view in &self {
+            unsafe { ctxt.clone().add_view(view) };
+        }
+    }
+}
+
+impl<C: IntoChildren> IntoChildren for Option<C> {
+    fn add_children(self, ctxt: &mut Build) {
+        if let Some(children) = self {
+            children.add_children(ctxt);
+        }
+    }
+}
+
+/// Wrapper for returning an iterator of views or widgets from a build function.
+#[derive(Debug)]
+pub struct Children<I>(pub I);
+
+impl<I> IntoChildren for Children<I>
+where
+    I: IntoIterator,
+    I::Item: Into<View>,
+{
+    fn add_children(self, ctxt: &mut Build) {
+        for view in self.0 {
+            unsafe { ctxt.clone().add_view(&view.into()) };
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! Headless driver for testing fehui widget trees.
+//!
+//! `TestApp` builds and lays out a root widget at a fixed size, finds widgets and simulates
+//! user interaction, clicks hit the computed bounds of the widgets. Handled events rebuild
+//! the UI like a host would.
+//!
+//! Widget ids change with each build, widgets need to be looked up again after interacting.
+
+use fehui::{
+    Build, Event, FehUI, Id, InputKey, Key, Layout, Layouter, Rect, StateSlot, View, Widget,
+};
+use std::fmt::{Debug, Write};
+use std::time::Duration;
+
+/// Marks the wrapped widget for lookup via `TestApp::find_test_id`.
+#[derive(Debug)]
+pub struct TestId {
+    pub id: String,
+    pub child: View,
+}
+
+impl TestId {
+    pub fn new<V: Into<View>>(id: &str, child: V) -> Self {
+        TestId {
+            id: id.to_string(),
+            child: child.into(),
+        }
+    }
+}
+
+impl Widget for TestId {
+    fn build(&self, mut build: Build) {
+        unsafe { build.add_view(&self.child) };
+    }
+
+    fn layout(&self, _: Layouter) -> Layout {
+        Layout::Pass
+    }
+}
+
+pub struct TestApp {
+    ui: FehUI,
+    width: f32,
+    height: f32,
+}
+
+impl TestApp {
+    /// Build the root widget and lay it out at the given size.
+    pub fn new<W: Widget>(root: W, width: f32, height: f32) -> Self {
+        let mut app = TestApp {
+            ui: FehUI::new(),
+            width,
+            height,
+        };
+        app.build(root);
+        app
+    }
+
+    pub fn ui(&self) -> &FehUI {
+        &self.ui
+    }
+
+    pub fn ui_mut(&mut self) -> &mut FehUI {
+        &mut self.ui
+    }
+
+    /// Build a new root widget.
+    pub fn build<W: Widget>(&mut self, root: W) {
+        self.ui.build(root);
+        self.ui.layout(self.width, self.height);
+    }
+
+    /// Build the current root widget again, e.g. after modifying states.
+    pub fn rebuild(&mut self) {
+        assert!(self.ui.rebuild(), "no root widget to rebuild");
+        self.ui.layout(self.width, self.height);
+    }
+
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.width = width;
+        self.height = height;
+        self.ui.layout(width, height);
+    }
+
+    /// First widget of type `W` in depth-first order.
+    pub fn find<W: Widget>(&self) -> Option<Id> {
+        self.find_all::<W>().into_iter().next()
+    }
+
+    pub fn find_all<W: Widget>(&self) -> Vec<Id> {
+        self.ui
+            .descendants()
+            .into_iter()
+            .filter(|&id| self.ui.widget(id).as_any().is::<W>())
+            .collect()
+    }
+
+    pub fn find_key(&self, key: Key) -> Option<Id> {
+        self.ui
+            .descendants()
+            .into_iter()
+            .find(|&id| self.ui.widget_key(id) == Some(key))
+    }
+
+    /// Widget wrapped by the `TestId` with the given id.
+    pub fn find_test_id(&self, test_id: &str) -> Option<Id> {
+        let wrapper = self.ui.descendants().into_iter().find(|&id| {
+            self.ui
+                .widget(id)
+                .as_any()
+                .downcast_ref::<TestId>()
+                .map_or(false, |widget| widget.id == test_id)
+        })?;
+        self.ui.children(wrapper).into_iter().next()
+    }
+
+    /// Access the widget, panics if it's not of type `W`.
+    pub fn get<W: Widget>(&self, id: Id) -> &W {
+        let widget = self.ui.widget(id);
+        widget.as_any().downcast_ref::<W>().unwrap_or_else(|| {
+            panic!(
+                "expected widget of type `{}`, found `{}`",
+                std::any::type_name::<W>(),
+                widget.type_name()
+            )
+        })
+    }
+
+    pub fn rect(&self, id: Id) -> Rect {
+        self.ui.rect(id)
+    }
+
+    /// Click at the center of the widget, returns `true` if the click has been handled.
+    pub fn click(&mut self, id: Id) -> bool {
+        let (x, y) = self.rect(id).center();
+        self.click_at(x, y)
+    }
+
+    pub fn click_at(&mut self, x: f32, y: f32) -> bool {
+        self.dispatch_at(x, y, &Event::Click { x, y })
+    }
+
+    /// Press a key while the widget is focused.
+    pub fn key_press(&mut self, id: Id, key: InputKey) -> bool {
+        self.dispatch(id, &Event::KeyPress(key))
+    }
+
+    /// Input text while the widget is focused.
+    pub fn type_text(&mut self, id: Id, text: &str) -> bool {
+        self.dispatch(id, &Event::Text(text.to_string()))
+    }
+
+    /// Dispatch an event to the widget, bubbling up to its ancestors until handled.
+    ///
+    /// Unlike `dispatch_at`, widgets covering the target don't receive the event.
+    pub fn dispatch(&mut self, id: Id, event: &Event) -> bool {
+        let handled = self.ui.dispatch_to(id, event);
+        if handled {
+            self.rebuild();
+        }
+        handled
+    }
+
+    /// Dispatch an event at the position, rebuilding the UI if it has been handled.
+    pub fn dispatch_at(&mut self, x: f32, y: f32, event: &Event) -> bool {
+        let handled = self.ui.dispatch(x, y, event);
+        if handled {
+            self.rebuild();
+        }
+        handled
+    }
+
+    /// Advance the fake clock of the UI and rebuild.
+    pub fn advance(&mut self, duration: Duration) {
+        let time = self.ui.time() + duration;
+        self.ui.set_time(time);
+        self.rebuild();
+    }
+
+    pub fn time(&self) -> Duration {
+        self.ui.time()
+    }
+
+    /// Access a state of a keyed widget, panics if it doesn't exist or is not of type `S`.
+    pub fn with_state<S, R, F>(&self, key: Key, slot: StateSlot, f: F) -> R
+    where
+        S: 'static,
+        F: FnOnce(&S) -> R,
+    {
+        let state = self
+            .ui
+            .state(key, slot)
+            .unwrap_or_else(|| panic!("no state {:?} for key {:?}", slot, key));
+        let state = state.borrow();
+        let state = state.downcast_ref::<S>().unwrap_or_else(|| {
+            panic!(
+                "state {:?} for key {:?} is not of type `{}`",
+                slot,
+                key,
+                std::any::type_name::<S>()
+            )
+        });
+        f(state)
+    }
+
+    pub fn assert_state<S: PartialEq + Debug + 'static>(
+        &self,
+        key: Key,
+        slot: StateSlot,
+        expected: &S,
+    ) {
+        self.with_state(key, slot, |state: &S| assert_eq!(state, expected));
+    }
+
+    /// Widget tree as indented list of type names.
+    pub fn tree(&self) -> String {
+        let mut tree = String::new();
+        if let Some(root) = self.ui.root {
+            self.write_tree(&mut tree, root, 0);
+        }
+        tree
+    }
+
+    /// Compare the widget tree against the expected one.
+    ///
+    /// Surrounding blank lines and the common indentation of `expected` are ignored.
+    pub fn assert_tree(&self, expected: &str) {
+        let tree = self.tree();
+        let actual = tree.lines().map(str::trim_end).collect::<Vec<_>>();
+        let expected = expected
+            .trim_start_matches('\n')
+            .trim_end()
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>();
+        let indent = expected
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let expected = expected
+            .iter()
+            .map(|line| line.get(indent..).unwrap_or(""))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actual.join("\n"),
+            expected.join("\n"),
+            "widget tree mismatch"
+        );
+    }
+
+    fn write_tree(&self, tree: &mut String, id: Id, depth: usize) {
+        let widget = self.ui.widget(id);
+        let _ = write!(
+            tree,
+            "{:indent$}{}",
+            "",
+            short_name(widget.type_name()),
+            indent = depth * 2
+        );
+        if let Some(test_id) = widget.as_any().downcast_ref::<TestId>() {
+            let _ = write!(tree, "({})", test_id.id);
+        }
+        tree.push('\n');
+
+        for child in self.ui.children(id) {
+            self.write_tree(tree, child, depth + 1);
+        }
+    }
+}
+
+// Strip module paths, e.g. `app::Row<app::Item>` -> `Row<Item>`.
+fn short_name(name: &str) -> String {
+    let mut short = String::new();
+    let mut segment = String::new();
+    for c in name.chars() {
+        match c {
+            '<' | '>' | ',' | ' ' | '(' | ')' | '[' | ']' | '&' => {
+                short.push_str(segment.rsplit("::").next().unwrap());
+                segment.clear();
+                short.push(c);
+            }
+            _ => segment.push(c),
+        }
+    }
+    short.push_str(segment.rsplit("::").next().unwrap());
+    short
+}
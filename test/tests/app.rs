@@ -0,0 +1,177 @@
+use fehui::{Build, Event, EventCtxt, InputKey, Key, Layout, Layouter, StateSlot, Widget};
+use fehui_derive::Widget;
+use fehui_test::{TestApp, TestId};
+use std::time::Duration;
+use stretch::geometry::Size;
+use stretch::style::{Dimension, Style};
+
+const COUNTER_KEY: Key = Key::Global(1);
+
+fn fill() -> Layout {
+    Layout::Flex(Style {
+        size: Size {
+            width: Dimension::Percent(1.0),
+            height: Dimension::Percent(1.0),
+        },
+        ..Style::default()
+    })
+}
+
+#[derive(Debug)]
+struct Panel;
+
+impl Widget for Panel {
+    fn build(&self, mut build: Build) {
+        unsafe { build.add(Counter { step: 2 }, Some(COUNTER_KEY)) };
+    }
+
+    fn layout(&self, _: Layouter) -> Layout {
+        fill()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct CounterState {
+    count: u32,
+}
+
+#[derive(Debug, Widget)]
+#[widget(build, state, layout = "fill", event = "on_event")]
+struct Counter {
+    step: u32,
+}
+
+impl Counter {
+    fn build(&self, state: &CounterState, _: &mut Build) -> TestId {
+        TestId::new("label", Label { value: state.count })
+    }
+
+    fn state(&self) -> CounterState {
+        CounterState { count: 0 }
+    }
+
+    fn fill(&self, _: Layouter) -> Layout {
+        fill()
+    }
+
+    fn on_event(&self, ctxt: &mut EventCtxt, event: &Event) -> bool {
+        match event {
+            Event::Click { .. } | Event::KeyPress(InputKey::Up) => {
+                let state = ctxt.state(StateSlot::Index(0)).unwrap();
+                let mut state = state.borrow_mut();
+                state.downcast_mut::<CounterState>().unwrap().count += self.step;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Widget)]
+#[widget(build, layout = "fill")]
+struct Label {
+    value: u32,
+}
+
+impl Label {
+    fn build(&self, _: &mut Build) -> Option<Label> {
+        None
+    }
+
+    fn fill(&self, _: Layouter) -> Layout {
+        fill()
+    }
+}
+
+#[derive(Debug, Widget)]
+#[widget(build, layout = "fill")]
+struct Clock;
+
+impl Clock {
+    fn build(&self, build: &mut Build) -> Label {
+        Label {
+            value: build.time().as_secs() as u32,
+        }
+    }
+
+    fn fill(&self, _: Layouter) -> Layout {
+        fill()
+    }
+}
+
+fn label(app: &TestApp) -> u32 {
+    let id = app.find_test_id("label").expect("no label");
+    app.get::<Label>(id).value
+}
+
+#[test]
+fn find_widgets() {
+    let app = TestApp::new(Panel, 200.0, 100.0);
+
+    let counter = app.find::<Counter>().expect("no counter");
+    assert_eq!(app.get::<Counter>(counter).step, 2);
+    assert_eq!(app.find_key(COUNTER_KEY), Some(counter));
+    assert_eq!(app.find_test_id("label"), app.find::<Label>());
+    assert_eq!(app.find_all::<Label>().len(), 1);
+    assert!(app.find::<Clock>().is_none());
+    assert_eq!(label(&app), 0);
+}
+
+#[test]
+fn tree() {
+    let app = TestApp::new(Panel, 200.0, 100.0);
+    app.assert_tree(
+        "
+        Panel
+          Counter
+            TestId(label)
+              Label
+        ",
+    );
+}
+
+#[test]
+fn click_updates_state() {
+    let mut app = TestApp::new(Panel, 200.0, 100.0);
+
+    let id = app.find::<Label>().unwrap();
+    assert!(app.click(id));
+    app.assert_state(COUNTER_KEY, StateSlot::Index(0), &CounterState { count: 2 });
+    assert_eq!(label(&app), 2);
+
+    // Ids change with each build.
+    let id = app.find::<Label>().unwrap();
+    assert!(app.click(id));
+    assert_eq!(label(&app), 4);
+}
+
+#[test]
+fn events_bubble_from_the_target() {
+    let mut app = TestApp::new(Panel, 200.0, 100.0);
+
+    let id = app.find::<Label>().unwrap();
+    assert!(app.key_press(id, InputKey::Up));
+    assert_eq!(label(&app), 2);
+
+    let id = app.find::<Label>().unwrap();
+    assert!(!app.key_press(id, InputKey::Down));
+    assert!(!app.type_text(id, "text"));
+    assert_eq!(label(&app), 2);
+}
+
+#[test]
+fn click_outside() {
+    let mut app = TestApp::new(Panel, 200.0, 100.0);
+    assert!(!app.click_at(250.0, 50.0));
+    assert_eq!(label(&app), 0);
+}
+
+#[test]
+fn advance() {
+    let mut app = TestApp::new(Clock, 200.0, 100.0);
+    assert_eq!(app.get::<Label>(app.find::<Label>().unwrap()).value, 0);
+
+    app.advance(Duration::from_secs(2));
+    assert_eq!(app.time(), Duration::from_secs(2));
+    assert_eq!(app.get::<Label>(app.find::<Label>().unwrap()).value, 2);
+}